@@ -0,0 +1,342 @@
+use std::{path::PathBuf, str::FromStr, time::Duration};
+
+use crate::{
+    cable::CableSelector,
+    calc::{ReportFormat, SessionConfig, HEARTBEAT_INTERVAL},
+    keyboard::CalcKey,
+    screen::RenderStyle,
+};
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum LinkChoice {
+    SilverLink(CableSelector),
+    SilverLinks(Vec<CableSelector>),
+    AllSilverLinks,
+    Socket(PathBuf),
+}
+
+// `i68apollo vars ...`, for moving variables on and off the calculator instead of using it as a keyboard
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum VarsCommand {
+    List,
+    Get(String),
+    Put(PathBuf),
+    Remove(String),
+}
+
+// `i68apollo backup ...`/`restore ...`, for the whole calculator at once
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum BackupCommand {
+    Backup(PathBuf),
+    Restore(PathBuf),
+}
+
+// `i68apollo clock ...`, for calculators with a real time clock
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum ClockCommand {
+    // just say how far off it is
+    Show,
+    Sync,
+}
+
+// where keys to press on the calculator come from
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum RemoteInput {
+    // text, typed out a character at a time
+    Stdin,
+    // a keyboard under /dev/input, passed through key for key
+    Evdev(PathBuf),
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum ArgsError {
+    HelpRequested,
+    MissingValue(String),
+    InvalidValue(String, String),
+    UnknownArgument(String),
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct ApolloArgs {
+    pub link: LinkChoice,
+    pub list_cables: bool,
+    pub merge_keyboards: bool,
+    // an unpacked i68soyuz release to install from, if soyuz is missing or out of date
+    pub soyuz_dir: Option<PathBuf>,
+    // start soyuz over the link instead of waiting for the user to
+    pub launch_soyuz: bool,
+    // what to ask soyuz for in the handshake
+    pub session_config: SessionConfig,
+    // how long to go without hearing from soyuz before deciding it's gone, if it's agreed to send heartbeats
+    pub link_timeout: Duration,
+    pub vars_command: Option<VarsCommand>,
+    // have `vars get` save programs as source text instead of as variable files
+    pub program_text: bool,
+    // where to save a screenshot, as .png or .pbm
+    pub screenshot: Option<PathBuf>,
+    // keep redrawing the calculator's screen in the terminal
+    pub mirror: bool,
+    pub mirror_style: RenderStyle,
+    pub mirror_fps: u32,
+    pub remote_input: Option<RemoteInput>,
+    pub backup_command: Option<BackupCommand>,
+    pub clock_command: Option<ClockCommand>,
+}
+impl ApolloArgs {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<ApolloArgs, ArgsError> {
+        let mut apollo_args = ApolloArgs {
+            link: LinkChoice::SilverLink(CableSelector::First),
+            list_cables: false,
+            merge_keyboards: false,
+            soyuz_dir: None,
+            launch_soyuz: false,
+            session_config: SessionConfig::default(),
+            link_timeout: Duration::from_secs(3),
+            vars_command: None,
+            program_text: false,
+            screenshot: None,
+            mirror: false,
+            mirror_style: RenderStyle::Braille,
+            mirror_fps: 4,
+            remote_input: None,
+            backup_command: None,
+            clock_command: None,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    return Err(ArgsError::HelpRequested);
+                }
+                "--list-cables" => {
+                    apollo_args.list_cables = true;
+                }
+                "--cable" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    let selector =
+                        parse_cable_selector(&value).ok_or(ArgsError::InvalidValue(arg, value))?;
+                    apollo_args.add_cable(selector);
+                }
+                "--cable-serial" => {
+                    let serial = args.next().ok_or(ArgsError::MissingValue(arg))?;
+                    apollo_args.add_cable(CableSelector::Serial(serial));
+                }
+                "--all-cables" => {
+                    apollo_args.link = LinkChoice::AllSilverLinks;
+                }
+                "--merge" => {
+                    apollo_args.merge_keyboards = true;
+                }
+                "--socket" => {
+                    let path = args.next().ok_or(ArgsError::MissingValue(arg))?;
+                    apollo_args.link = LinkChoice::Socket(PathBuf::from(path));
+                }
+                "--soyuz-dir" => {
+                    let path = args.next().ok_or(ArgsError::MissingValue(arg))?;
+                    apollo_args.soyuz_dir = Some(PathBuf::from(path));
+                }
+                "--launch" => {
+                    apollo_args.launch_soyuz = true;
+                }
+                "--quit-key" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    apollo_args.session_config.quit_key = CalcKey::from_str(&value)
+                        .map_err(|_| ArgsError::InvalidValue(arg, value))?;
+                }
+                "--scan-rate" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    apollo_args.session_config.scan_rate = match value.parse() {
+                        Ok(scan_rate) if scan_rate > 0 => scan_rate,
+                        _ => {
+                            return Err(ArgsError::InvalidValue(arg, value));
+                        }
+                    };
+                }
+                "--report-format" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    apollo_args.session_config.report_format = match value.as_str() {
+                        "full" => ReportFormat::FullMatrix,
+                        "delta" => ReportFormat::Delta,
+                        _ => {
+                            return Err(ArgsError::InvalidValue(arg, value));
+                        }
+                    };
+                }
+                // anything shorter than a couple of heartbeats would go off on a perfectly healthy link
+                "--link-timeout" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    apollo_args.link_timeout = match value.parse() {
+                        Ok(secs) if Duration::from_secs(secs) >= 2 * HEARTBEAT_INTERVAL => {
+                            Duration::from_secs(secs)
+                        }
+                        _ => {
+                            return Err(ArgsError::InvalidValue(arg, value));
+                        }
+                    };
+                }
+                "--text" => {
+                    apollo_args.program_text = true;
+                }
+                "vars" => {
+                    let command = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    let mut operand = || {
+                        args.next()
+                            .ok_or(ArgsError::MissingValue(format!("{arg} {command}")))
+                    };
+                    apollo_args.vars_command = Some(match command.as_str() {
+                        "ls" => VarsCommand::List,
+                        "get" => VarsCommand::Get(operand()?),
+                        "put" => VarsCommand::Put(PathBuf::from(operand()?)),
+                        "rm" => VarsCommand::Remove(operand()?),
+                        _ => {
+                            return Err(ArgsError::InvalidValue(arg, command));
+                        }
+                    });
+                }
+                "screenshot" => {
+                    let path =
+                        PathBuf::from(args.next().ok_or(ArgsError::MissingValue(arg.clone()))?);
+                    match path.extension().and_then(|extension| extension.to_str()) {
+                        Some("png" | "pbm") => {
+                            apollo_args.screenshot = Some(path);
+                        }
+                        _ => {
+                            return Err(ArgsError::InvalidValue(arg, path.display().to_string()));
+                        }
+                    }
+                }
+                "backup" => {
+                    let path = args.next().ok_or(ArgsError::MissingValue(arg))?;
+                    apollo_args.backup_command = Some(BackupCommand::Backup(PathBuf::from(path)));
+                }
+                "restore" => {
+                    let path = args.next().ok_or(ArgsError::MissingValue(arg))?;
+                    apollo_args.backup_command = Some(BackupCommand::Restore(PathBuf::from(path)));
+                }
+                "clock" => {
+                    let command = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    apollo_args.clock_command = Some(match command.as_str() {
+                        "show" => ClockCommand::Show,
+                        "sync" => ClockCommand::Sync,
+                        _ => {
+                            return Err(ArgsError::InvalidValue(arg, command));
+                        }
+                    });
+                }
+                "type" => {
+                    apollo_args.remote_input = Some(RemoteInput::Stdin);
+                }
+                "remote" => {
+                    let path = args.next().ok_or(ArgsError::MissingValue(arg))?;
+                    apollo_args.remote_input = Some(RemoteInput::Evdev(PathBuf::from(path)));
+                }
+                "mirror" => {
+                    apollo_args.mirror = true;
+                }
+                "--blocks" => {
+                    apollo_args.mirror_style = RenderStyle::HalfBlocks;
+                }
+                "--fps" => {
+                    let value = args.next().ok_or(ArgsError::MissingValue(arg.clone()))?;
+                    apollo_args.mirror_fps = match value.parse() {
+                        Ok(fps) if fps > 0 => fps,
+                        _ => {
+                            return Err(ArgsError::InvalidValue(arg, value));
+                        }
+                    };
+                }
+                _ => {
+                    return Err(ArgsError::UnknownArgument(arg));
+                }
+            }
+        }
+
+        Ok(apollo_args)
+    }
+
+    // --cable can be given more than once, in which case we drive all of them
+    fn add_cable(&mut self, selector: CableSelector) {
+        self.link = match &self.link {
+            LinkChoice::SilverLink(CableSelector::First)
+            | LinkChoice::AllSilverLinks
+            | LinkChoice::Socket(_) => LinkChoice::SilverLink(selector),
+            LinkChoice::SilverLink(previous) => {
+                LinkChoice::SilverLinks(vec![previous.clone(), selector])
+            }
+            LinkChoice::SilverLinks(previous) => {
+                let mut selectors = previous.clone();
+                selectors.push(selector);
+                LinkChoice::SilverLinks(selectors)
+            }
+        };
+    }
+}
+
+// either BUS:ADDRESS as shown by lsusb, or a port path like 1-3.2
+fn parse_cable_selector(value: &str) -> Option<CableSelector> {
+    if let Some((bus_number, address)) = value.split_once(':') {
+        return Some(CableSelector::BusAddress(
+            bus_number.parse().ok()?,
+            address.parse().ok()?,
+        ));
+    }
+
+    let (bus_number, ports) = value.split_once('-')?;
+    bus_number.parse::<u8>().ok()?;
+    for port in ports.split('.') {
+        port.parse::<u8>().ok()?;
+    }
+
+    Some(CableSelector::PortPath(value.to_string()))
+}
+
+pub fn usage() -> &'static str {
+    "usage: i68apollo [--list-cables] [--cable BUS:ADDRESS|PORT-PATH]... [--cable-serial SERIAL]... [--all-cables]
+                 [--merge] [--socket PATH] [--soyuz-dir DIR] [--launch] [--quit-key KEY] [--scan-rate N]
+                 [--report-format full|delta] [--link-timeout SECS]
+       i68apollo [--cable BUS:ADDRESS|PORT-PATH] [--cable-serial SERIAL] [--text] vars ls|get NAME|put FILE|rm NAME
+       i68apollo [--cable BUS:ADDRESS|PORT-PATH] [--cable-serial SERIAL] screenshot FILE.png|FILE.pbm
+       i68apollo [--cable BUS:ADDRESS|PORT-PATH] [--cable-serial SERIAL] [--fps N] [--blocks] mirror
+       i68apollo [--cable BUS:ADDRESS|PORT-PATH] [--cable-serial SERIAL] type|remote DEVICE
+       i68apollo [--cable BUS:ADDRESS|PORT-PATH] [--cable-serial SERIAL] backup|restore FILE.tar
+       i68apollo [--cable BUS:ADDRESS|PORT-PATH] [--cable-serial SERIAL] clock show|sync
+
+commands:
+  vars ls                 list the calculator's variables
+  vars get NAME           save variable NAME (or FOLDER\\NAME) to a file named after it in the current directory
+  vars put FILE           send the variables in FILE (group files too), archived if they were when saved. A .txt
+                          FILE is TI-BASIC source, sent as a program named after the file
+  vars rm NAME            delete variable NAME (or FOLDER\\NAME) from the calculator
+  screenshot FILE         save what's on the calculator's screen to FILE, as a PNG or PBM going by its extension
+  mirror                  keep showing the calculator's screen in the terminal until interrupted
+  backup FILE             save every variable on the calculator to FILE, a tarball, along with a manifest
+  restore FILE            send every variable in a backup made by `backup` back to the calculator
+  clock show              say how far the calculator's clock is from this computer's. 89 Titanium only
+  clock sync              set the calculator's clock from this computer's, then check it took
+  type                    type what comes in on stdin into the calculator, a line at a time
+  remote DEVICE           pass keys from the keyboard at DEVICE (e.g. /dev/input/event3) through to the calculator.
+                          The keyboard is taken over until PAUSE is pressed
+
+options:
+  --list-cables           list every connected SilverLink cable and exit
+  --cable SELECTOR        use the SilverLink at BUS:ADDRESS (e.g. 1:5) or PORT-PATH (e.g. 1-3.2) instead of the first
+  --cable-serial SERIAL   use the SilverLink with this serial number
+  --all-cables            use every connected SilverLink, each calculator getting its own virtual keyboard
+  --merge                 when using several calculators, have them all type into one shared virtual keyboard
+  --socket PATH           connect to i68soyuz-sim listening on PATH instead of a SilverLink cable
+  --soyuz-dir DIR         install i68soyuz from the release in DIR first if the calculator's copy is missing or differs.
+                          Releases come from the i68soyuz repository, none are built into apollo
+  --launch                start i68soyuz on the calculator from its home screen, no keypresses needed
+  --quit-key KEY          end the session on KEY instead of ON, e.g. ESC or F5. Soyuz may still insist on ON
+  --scan-rate N           ask soyuz to scan the keyboard at most N times a second, 20 by default
+  --report-format FORMAT  have soyuz send the whole key matrix every scan (full) or just what changed (delta, the
+                          default). Soyuz falls back to full if it has to
+  --link-timeout SECS     give up on soyuz after SECS seconds without a word from it, 3 by default and at least 2.
+                          Any keys it was holding down get let go
+  --text                  have `vars get` save programs as UTF-8 source, NAME.txt, that `vars put` can send back. 89/92+
+                          programs that have been run have lost their source, so those are saved as the variable file
+  --fps N                 how many times a second mirror redraws the screen, 4 by default. The calculator tops out at
+                          a few a second
+  --blocks                have mirror draw with half blocks instead of braille, for fonts without braille"
+}
@@ -0,0 +1,227 @@
+use debug_print::debug_eprintln;
+use tar::{read_tar, write_tar, TarError};
+
+use crate::{
+    calc::{apollo_version, model_name, MACHINE_ID_TI83P},
+    formats::{is_archived, type_name, FormatError, TiVar, VarFile, TI89_ATTR_LOCKED},
+    link::LinkTransport,
+    vars::{get_var, get_version, list_vars, send_var, DirEntry, TransferError, FLASH_APP},
+};
+
+pub mod tar;
+
+// everything on a calculator, in one tarball: a manifest saying what it came off, then each variable as the same file
+// TI's software would save it as. 89/92+ variables go in a directory per folder
+
+const MANIFEST_NAME: &str = "manifest.txt";
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum BackupError {
+    BadArchive(TarError),
+    NoManifest,
+    // the line that didn't make sense
+    BadManifest(String),
+    // (path in the archive, why)
+    BadFile(String, FormatError),
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Backup {
+    pub machine_id: u8,
+    // not every OS will say
+    pub os_version: Option<(u8, u8)>,
+    pub vars: Vec<TiVar>,
+    // FlashApps can't be pulled off over the silent link, so these are just a note of what was there
+    pub apps: Vec<DirEntry>,
+}
+impl Backup {
+    pub fn to_tar(&self, mtime: u64) -> Vec<u8> {
+        let mut files = Vec::new();
+        let mut var_lines = Vec::new();
+
+        for var in &self.vars {
+            let path = self.var_path(var, &files);
+
+            let flags = match var.attr {
+                attr if is_archived(self.machine_id, attr) => " archived",
+                TI89_ATTR_LOCKED if self.machine_id != MACHINE_ID_TI83P => " locked",
+                _ => "",
+            };
+            var_lines.push(format!(
+                "var {} {} {}{}",
+                path,
+                type_name(self.machine_id, var.var_type).unwrap_or("?"),
+                var.data.len(),
+                flags
+            ));
+
+            let file = VarFile::single(self.machine_id, "Backed up by i68apollo", var.clone());
+            files.push((path, file.to_bytes()));
+        }
+
+        let mut manifest = self.manifest_header();
+        manifest.extend(var_lines);
+        manifest.extend(
+            self.apps
+                .iter()
+                .map(|app| format!("app {} {}", app.name, app.size)),
+        );
+
+        files.insert(
+            0,
+            (
+                MANIFEST_NAME.to_string(),
+                (manifest.join("\n") + "\n").into_bytes(),
+            ),
+        );
+        write_tar(&files, mtime)
+    }
+
+    pub fn from_tar(bytes: &[u8]) -> Result<Backup, BackupError> {
+        let mut files = read_tar(bytes)
+            .map_err(BackupError::BadArchive)?
+            .into_iter();
+
+        let manifest = match files.next() {
+            Some((path, manifest)) if path == MANIFEST_NAME => manifest,
+            _ => {
+                return Err(BackupError::NoManifest);
+            }
+        };
+
+        let mut backup = Backup {
+            machine_id: 0,
+            os_version: None,
+            vars: Vec::new(),
+            apps: Vec::new(),
+        };
+        for line in String::from_utf8_lossy(&manifest).lines() {
+            backup.read_manifest_line(line)?;
+        }
+        if backup.machine_id == 0 {
+            return Err(BackupError::NoManifest);
+        }
+
+        // the manifest lists the variables too, but the files are what actually get restored
+        for (path, bytes) in files {
+            let var = VarFile::parse(&bytes)
+                .and_then(VarFile::into_single)
+                .map_err(|e| BackupError::BadFile(path.clone(), e))?;
+            backup.vars.push(var);
+        }
+
+        Ok(backup)
+    }
+
+    fn manifest_header(&self) -> Vec<String> {
+        let (major, minor, patch) = apollo_version();
+
+        vec![
+            format!("i68apollo backup, version {major}.{minor}.{patch}"),
+            format!(
+                "model: {}",
+                model_name(self.machine_id).unwrap_or("unknown")
+            ),
+            format!("machine id: {}", self.machine_id),
+            match self.os_version {
+                Some((major, minor)) => format!("os version: {major}.{minor:02}"),
+                None => "os version: unknown".to_string(),
+            },
+            String::new(),
+        ]
+    }
+
+    fn read_manifest_line(&mut self, line: &str) -> Result<(), BackupError> {
+        let bad_line = || BackupError::BadManifest(line.to_string());
+
+        if let Some(machine_id) = line.strip_prefix("machine id: ") {
+            self.machine_id = machine_id.parse().map_err(|_| bad_line())?;
+        } else if let Some(os_version) = line.strip_prefix("os version: ") {
+            self.os_version = os_version
+                .split_once('.')
+                .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)));
+        } else if let Some(app) = line.strip_prefix("app ") {
+            let (name, size) = app.rsplit_once(' ').ok_or_else(bad_line)?;
+            self.apps.push(DirEntry {
+                folder: String::new(),
+                name: name.to_string(),
+                var_type: FLASH_APP,
+                attr: 0,
+                size: size.parse().map_err(|_| bad_line())?,
+            });
+        }
+
+        Ok(())
+    }
+
+    // folder/name.ext, or just name.ext on the 83+. 83+ names can have tokens in them, e.g. for lists, so anything
+    // that wouldn't make a sensible file name gets replaced, with a number tacked on if that makes two the same
+    fn var_path(&self, var: &TiVar, taken: &[(String, Vec<u8>)]) -> String {
+        let file_name = VarFile::single(self.machine_id, "", var.clone())
+            .file_name()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let dir = if var.folder.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", var.folder)
+        };
+
+        let mut path = format!("{dir}{file_name}");
+        let mut n = 1;
+        while taken.iter().any(|(taken, _)| *taken == path) {
+            n += 1;
+            path = format!("{dir}{n}-{file_name}");
+        }
+
+        path
+    }
+}
+
+pub fn backup(link: &mut dyn LinkTransport, machine_id: u8) -> Result<Backup, TransferError> {
+    let os_version = match get_version(link, machine_id) {
+        Ok(os_version) => Some(os_version),
+        Err(e) => {
+            debug_eprintln!("backup: no OS version. Reason: {e:?}");
+            None
+        }
+    };
+
+    let (apps, entries): (Vec<DirEntry>, Vec<DirEntry>) = list_vars(link, machine_id)?
+        .into_iter()
+        .partition(|entry| entry.var_type == FLASH_APP);
+
+    let mut vars = Vec::new();
+    for entry in &entries {
+        debug_eprintln!("backup: getting {}\\{}", entry.folder, entry.name);
+        vars.push(get_var(link, machine_id, entry)?);
+    }
+
+    Ok(Backup {
+        machine_id,
+        os_version,
+        vars,
+        apps,
+    })
+}
+
+// keeps going past variables the calculator won't take, so one locked variable doesn't stop the rest. Gives back how
+// each variable went, in the same order as `backup.vars`
+pub fn restore(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    backup: &Backup,
+) -> Vec<Result<(), TransferError>> {
+    backup
+        .vars
+        .iter()
+        .map(|var| send_var(link, machine_id, var))
+        .collect()
+}
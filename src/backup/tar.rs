@@ -0,0 +1,118 @@
+// just enough of ustar to write a flat list of files and read them back. Anything that isn't a plain file (directories,
+// links, GNU's long name extensions) gets skipped on the way in
+
+const BLOCK_LEN: usize = 512;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum TarError {
+    Truncated,
+    // (stored, calculated)
+    BadChecksum(u32, u32),
+    // a number field that isn't octal
+    BadHeader,
+}
+
+// (path, contents) pairs. Paths have to fit in the 100 byte name field, which ours always do
+pub fn write_tar(files: &[(String, Vec<u8>)], mtime: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    for (path, contents) in files {
+        let mut header = [0u8; BLOCK_LEN];
+        put_field(&mut header[0..100], path.as_bytes());
+        put_field(&mut header[100..108], b"0000644");
+        put_field(&mut header[108..116], b"0000000");
+        put_field(&mut header[116..124], b"0000000");
+        put_field(
+            &mut header[124..136],
+            format!("{:011o}", contents.len()).as_bytes(),
+        );
+        put_field(&mut header[136..148], format!("{mtime:011o}").as_bytes());
+        header[156] = b'0';
+        put_field(&mut header[257..263], b"ustar");
+        put_field(&mut header[263..265], b"00");
+
+        let checksum = header_checksum(&header);
+        put_field(
+            &mut header[148..156],
+            format!("{checksum:06o}\0 ").as_bytes(),
+        );
+
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(contents);
+        bytes.resize(bytes.len().next_multiple_of(BLOCK_LEN), 0);
+    }
+
+    // two empty blocks mark the end
+    bytes.resize(bytes.len() + BLOCK_LEN * 2, 0);
+    bytes
+}
+
+pub fn read_tar(bytes: &[u8]) -> Result<Vec<(String, Vec<u8>)>, TarError> {
+    let mut files = Vec::new();
+
+    let mut offset = 0;
+    loop {
+        let header = bytes
+            .get(offset..offset + BLOCK_LEN)
+            .ok_or(TarError::Truncated)?;
+        if header.iter().all(|byte| *byte == 0) {
+            break;
+        }
+
+        let stored = parse_octal(&header[148..156])? as u32;
+        let calculated = header_checksum(header);
+        if stored != calculated {
+            return Err(TarError::BadChecksum(stored, calculated));
+        }
+
+        let size = parse_octal(&header[124..136])? as usize;
+        let contents = bytes
+            .get(offset + BLOCK_LEN..offset + BLOCK_LEN + size)
+            .ok_or(TarError::Truncated)?;
+
+        if header[156] == b'0' || header[156] == 0 {
+            let name = field_string(&header[0..100]);
+            let path = match field_string(&header[345..500]) {
+                prefix if prefix.is_empty() => name,
+                prefix => format!("{prefix}/{name}"),
+            };
+            files.push((path, contents.to_vec()));
+        }
+
+        offset += BLOCK_LEN + size.next_multiple_of(BLOCK_LEN);
+    }
+
+    Ok(files)
+}
+
+fn put_field(field: &mut [u8], value: &[u8]) {
+    let len = value.len().min(field.len());
+    field[..len].copy_from_slice(&value[..len]);
+}
+
+fn field_string(field: &[u8]) -> String {
+    String::from_utf8_lossy(field.split(|byte| *byte == 0).next().unwrap_or_default()).into_owned()
+}
+
+// numbers are octal text, ended by a NUL or a space
+fn parse_octal(field: &[u8]) -> Result<u64, TarError> {
+    let digits = field_string(field);
+    let digits = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+
+    u64::from_str_radix(digits, 8).map_err(|_| TarError::BadHeader)
+}
+
+// the sum of every byte in the header, counting the checksum field itself as spaces
+fn header_checksum(header: &[u8]) -> u32 {
+    header
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| match i {
+            148..156 => b' ' as u32,
+            _ => *byte as u32,
+        })
+        .sum()
+}
@@ -0,0 +1,366 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixListener,
+    path::PathBuf,
+    process,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use i68apollo::{
+    calc::{
+        apollo_version, delta_record, encode_frame, key_layout, KeyLayout, ReportFormat,
+        SessionConfig, DELTA_KEYFRAME, FLAG_FRAMED, FLAG_HEARTBEAT, FLAG_SHUTDOWN,
+        HEARTBEAT_INTERVAL, MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P,
+        SESSION_CONFIG_LEN, SHUTDOWN_REQUEST,
+    },
+    keyboard::CalcKey,
+};
+
+const DEFAULT_SOCKET_PATH: &str = "/tmp/i68soyuz-sim.sock";
+
+struct SimArgs {
+    machine_id: u8,
+    socket_path: PathBuf,
+    script: Option<PathBuf>,
+}
+
+fn usage() {
+    eprintln!("usage: i68soyuz-sim [--model 89|92p|83p] [--socket PATH] [--script FILE]");
+    eprintln!();
+    eprintln!("Key events are read from FILE, or stdin if no script is given, one per line:");
+    eprintln!("  press KEY      hold KEY down");
+    eprintln!("  release KEY    let KEY go");
+    eprintln!("  tap KEY        press and release KEY");
+    eprintln!("  sleep MS       wait MS milliseconds before the next line");
+    eprintln!("KEY is a CalcKey name, e.g. A, _1, ENTER1, CursorUp, ON");
+}
+
+fn parse_args() -> Result<SimArgs, String> {
+    let mut sim_args = SimArgs {
+        machine_id: MACHINE_ID_TI89,
+        socket_path: PathBuf::from(DEFAULT_SOCKET_PATH),
+        script: None,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--model" => {
+                sim_args.machine_id = match value()?.as_str() {
+                    "89" => MACHINE_ID_TI89,
+                    "92p" => MACHINE_ID_TI92P,
+                    "83p" => MACHINE_ID_TI83P,
+                    other => return Err(format!("unknown model {other}")),
+                };
+            }
+            "--socket" => sim_args.socket_path = PathBuf::from(value()?),
+            "--script" => sim_args.script = Some(PathBuf::from(value()?)),
+            other => return Err(format!("unknown argument {other}")),
+        }
+    }
+
+    Ok(sim_args)
+}
+
+// how many delta reports go by between keyframes
+const KEYFRAME_INTERVAL: usize = 16;
+
+// the key matrix exactly as soyuz would scan it, in the same layout apollo decodes
+struct SimMatrix {
+    matrix: Vec<u8>,
+    layout: KeyLayout,
+    report_format: ReportFormat,
+    // None until the first keyframe's gone out
+    reports_since_keyframe: Option<usize>,
+    // the sequence number for the next frame, if reports are framed
+    next_seq: Option<u8>,
+}
+impl SimMatrix {
+    fn new(machine_id: u8, config: &SessionConfig) -> SimMatrix {
+        let (matrix_len, layout) = key_layout(machine_id).unwrap();
+        SimMatrix {
+            matrix: vec![0; matrix_len],
+            layout,
+            report_format: config.report_format,
+            reports_since_keyframe: None,
+            next_seq: (config.flags & FLAG_FRAMED != 0).then_some(0),
+        }
+    }
+
+    // what soyuz would send for the change
+    fn set_key(&mut self, key: CalcKey, pressed: bool) -> Result<Vec<u8>, String> {
+        let ((row, col), _) = self
+            .layout
+            .iter()
+            .find(|(_, k)| *k == key)
+            .ok_or(format!("{key:?} isn't on this calculator"))?;
+
+        if pressed {
+            self.matrix[*row] |= 1 << col;
+        } else {
+            self.matrix[*row] &= !(1 << col);
+        }
+
+        let report = match (self.report_format, self.reports_since_keyframe) {
+            (ReportFormat::FullMatrix, _) => self.matrix.clone(),
+            (ReportFormat::Delta, Some(reports)) if reports < KEYFRAME_INTERVAL => {
+                self.reports_since_keyframe = Some(reports + 1);
+                vec![1, delta_record(*row, *col, pressed)]
+            }
+            (ReportFormat::Delta, _) => self.keyframe(),
+        };
+
+        Ok(self.frame(report))
+    }
+
+    // what soyuz sends when nothing's changed: the same matrix again, or a delta with no records in it
+    fn heartbeat(&mut self) -> Vec<u8> {
+        let report = match (self.report_format, self.reports_since_keyframe) {
+            (ReportFormat::FullMatrix, _) => self.matrix.clone(),
+            (ReportFormat::Delta, Some(_)) => vec![0],
+            (ReportFormat::Delta, None) => self.keyframe(),
+        };
+
+        self.frame(report)
+    }
+
+    fn keyframe(&mut self) -> Vec<u8> {
+        self.reports_since_keyframe = Some(0);
+        [&[DELTA_KEYFRAME], self.matrix.as_slice()].concat()
+    }
+
+    fn frame(&mut self, report: Vec<u8>) -> Vec<u8> {
+        match self.next_seq {
+            Some(seq) => {
+                self.next_seq = Some(seq.wrapping_add(1));
+                encode_frame(seq, &report)
+            }
+            None => report,
+        }
+    }
+}
+
+// the configuration agreed on, if it got that far
+fn handshake(stream: &mut impl ReadWrite, machine_id: u8) -> io::Result<Option<SessionConfig>> {
+    let (apollo_ver_major, apollo_ver_minor, apollo_ver_patch) = apollo_version();
+
+    stream.write_all(&[0x50])?;
+    stream.write_all(&[apollo_ver_major, apollo_ver_minor, apollo_ver_patch])?;
+
+    let mut apollo_ver = [0; 3];
+    stream.read_exact(&mut apollo_ver)?;
+    eprintln!(
+        "apollo ver: {}.{}.{}",
+        apollo_ver[0], apollo_ver[1], apollo_ver[2]
+    );
+    if apollo_ver[0] != apollo_ver_major || apollo_ver[1] != apollo_ver_minor {
+        eprintln!("Version mismatch");
+        return Ok(None);
+    }
+
+    stream.write_all(&[machine_id])?;
+
+    // goes along with whatever apollo asks for, bar optional features it doesn't have. Anything it can't make sense of
+    // gets full matrices and the default quit key instead
+    let mut request = [0; SESSION_CONFIG_LEN];
+    stream.read_exact(&mut request)?;
+    let config = match SessionConfig::decode(machine_id, &request) {
+        Some(config) => SessionConfig {
+            flags: config.flags & (FLAG_FRAMED | FLAG_HEARTBEAT | FLAG_SHUTDOWN),
+            ..config
+        },
+        None => SessionConfig {
+            report_format: ReportFormat::FullMatrix,
+            flags: 0,
+            scan_rate: request[3],
+            ..SessionConfig::default()
+        },
+    };
+    eprintln!("config: {config:?}");
+    stream.write_all(&config.encode(machine_id).unwrap())?;
+
+    Ok(Some(config))
+}
+
+enum ScriptLine {
+    Keys(Vec<(CalcKey, bool)>),
+    Sleep(Duration),
+    Nothing,
+}
+
+fn parse_script_line(line: &str) -> Result<ScriptLine, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(ScriptLine::Nothing);
+    }
+
+    let (command, operand) = line.split_once(' ').unwrap_or((line, ""));
+    let operand = operand.trim();
+
+    if command == "sleep" {
+        return match operand.parse::<u64>() {
+            Ok(ms) => Ok(ScriptLine::Sleep(Duration::from_millis(ms))),
+            Err(_) => Err(format!("bad sleep duration {operand}")),
+        };
+    }
+
+    let key = CalcKey::from_str(operand).map_err(|_| format!("unknown key {operand}"))?;
+    match command {
+        "press" => Ok(ScriptLine::Keys(vec![(key, true)])),
+        "release" => Ok(ScriptLine::Keys(vec![(key, false)])),
+        "tap" => Ok(ScriptLine::Keys(vec![(key, true), (key, false)])),
+        _ => Err(format!("unknown command {command}")),
+    }
+}
+
+// a report goes out under the matrix's lock, so heartbeats can't land halfway through one
+fn play_script(
+    stream: &mut impl ReadWrite,
+    matrix: &Mutex<SimMatrix>,
+    script: Box<dyn BufRead>,
+) -> io::Result<()> {
+    for (line_number, line) in script.lines().enumerate() {
+        let keys = match parse_script_line(&line?) {
+            Ok(ScriptLine::Keys(keys)) => keys,
+            Ok(ScriptLine::Sleep(duration)) => {
+                thread::sleep(duration);
+                continue;
+            }
+            Ok(ScriptLine::Nothing) => continue,
+            Err(e) => {
+                eprintln!("line {}: {e}", line_number + 1);
+                continue;
+            }
+        };
+
+        for (key, pressed) in keys {
+            let mut matrix = matrix.lock().unwrap();
+            match matrix.set_key(key, pressed) {
+                Ok(report) => stream.write_all(&report)?,
+                Err(e) => {
+                    eprintln!("line {}: {e}", line_number + 1);
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// until the script's done. A write failing means apollo's gone, which the script will find out for itself
+fn send_heartbeats(stream: &mut impl Write, matrix: &Mutex<SimMatrix>, done: &AtomicBool) {
+    loop {
+        thread::sleep(HEARTBEAT_INTERVAL);
+        if done.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let mut matrix = matrix.lock().unwrap();
+        if stream.write_all(&matrix.heartbeat()).is_err() {
+            break;
+        }
+    }
+}
+
+// the only thing apollo sends mid-session. Whatever the script was up to, that's the end of it
+fn await_shutdown(mut stream: impl Read, socket_path: PathBuf) {
+    let mut byte = [0];
+    while stream.read_exact(&mut byte).is_ok() {
+        if byte[0] == SHUTDOWN_REQUEST {
+            eprintln!("apollo asked soyuz to exit");
+            let _ = fs::remove_file(&socket_path);
+            process::exit(0);
+        }
+    }
+}
+
+trait ReadWrite: Read + Write {}
+impl<T: Read + Write> ReadWrite for T {}
+
+fn main() -> Result<(), ()> {
+    let sim_args = match parse_args() {
+        Ok(sim_args) => sim_args,
+        Err(e) => {
+            eprintln!("{e}\n");
+            usage();
+            return Err(());
+        }
+    };
+
+    let script: Box<dyn BufRead> = match &sim_args.script {
+        Some(path) => match fs::File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(e) => {
+                eprintln!("Couldn't open script {}. Reason: {e}", path.display());
+                return Err(());
+            }
+        },
+        None => Box::new(BufReader::new(io::stdin())),
+    };
+
+    let _ = fs::remove_file(&sim_args.socket_path);
+    let listener = match UnixListener::bind(&sim_args.socket_path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!(
+                "Couldn't bind {}. Reason: {e}",
+                sim_args.socket_path.display()
+            );
+            return Err(());
+        }
+    };
+
+    println!("i68 simulated foreign component \"soyuz-sim\"\n");
+    println!(
+        "Start apollo with \"--socket {}\"",
+        sim_args.socket_path.display()
+    );
+
+    let (mut stream, _) = listener.accept().map_err(|e| {
+        eprintln!("Couldn't accept connection. Reason: {e}");
+    })?;
+
+    let session = match handshake(&mut stream, sim_args.machine_id) {
+        Ok(Some(config)) => {
+            let matrix = Mutex::new(SimMatrix::new(sim_args.machine_id, &config));
+            let done = AtomicBool::new(false);
+            if config.flags & FLAG_SHUTDOWN != 0 {
+                let shutdown_stream = stream.try_clone().map_err(|e| {
+                    eprintln!("Couldn't listen for apollo shutting down. Reason: {e}");
+                })?;
+                let socket_path = sim_args.socket_path.clone();
+                thread::spawn(move || await_shutdown(shutdown_stream, socket_path));
+            }
+            thread::scope(|scope| {
+                if config.flags & FLAG_HEARTBEAT != 0 {
+                    let mut heartbeat_stream = stream.try_clone()?;
+                    let (matrix, done) = (&matrix, &done);
+                    scope.spawn(move || send_heartbeats(&mut heartbeat_stream, matrix, done));
+                }
+
+                let result = play_script(&mut stream, &matrix, script);
+                done.store(true, Ordering::Relaxed);
+                result
+            })
+        }
+        other => other.map(|_| ()),
+    };
+
+    let _ = fs::remove_file(&sim_args.socket_path);
+
+    if let Err(e) = session {
+        eprintln!("Link error. Reason: {e}");
+        return Err(());
+    }
+
+    Ok(())
+}
@@ -1,7 +1,11 @@
 use std::time::Duration;
 
 use debug_print::debug_eprintln;
-use rusb::{DeviceHandle, DeviceList, GlobalContext};
+use rusb::{Device, DeviceHandle, DeviceList, GlobalContext};
+
+use crate::link::{LinkIoError, LinkStats, LinkTransport};
+
+pub mod hotplug;
 
 // constants sourced from lsusb
 const WRITE_ENDPOINT: u8 = 0x02;
@@ -19,19 +23,93 @@ pub enum CableCreationError {
     ClaimInterfaceFailed(rusb::Error),
 }
 
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum CableIoError {
+    Timeout,
+    Disconnected,
+    Stall,
+    Overflow,
+    Other(rusb::Error),
+}
+impl From<rusb::Error> for CableIoError {
+    fn from(e: rusb::Error) -> Self {
+        match e {
+            rusb::Error::Timeout => CableIoError::Timeout,
+            rusb::Error::NoDevice => CableIoError::Disconnected,
+            rusb::Error::Pipe => CableIoError::Stall,
+            rusb::Error::Overflow => CableIoError::Overflow,
+            _ => CableIoError::Other(e),
+        }
+    }
+}
+
+// where a SilverLink is plugged in, and what it calls itself
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct CableInfo {
+    pub bus_number: u8,
+    pub address: u8,
+    pub port_numbers: Vec<u8>,
+    pub serial: Option<String>,
+}
+impl CableInfo {
+    // same format as sysfs, e.g. "1-3" or "1-3.2". Unlike the address, this survives unplugging and replugging the
+    // cable into the same port
+    pub fn port_path(&self) -> String {
+        let ports = self
+            .port_numbers
+            .iter()
+            .map(|port| port.to_string())
+            .collect::<Vec<String>>()
+            .join(".");
+
+        format!("{}-{}", self.bus_number, ports)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum CableSelector {
+    First,
+    BusAddress(u8, u8),
+    PortPath(String),
+    Serial(String),
+}
+impl CableSelector {
+    pub fn matches(&self, info: &CableInfo) -> bool {
+        match self {
+            CableSelector::First => true,
+            CableSelector::BusAddress(bus_number, address) => {
+                info.bus_number == *bus_number && info.address == *address
+            }
+            CableSelector::PortPath(port_path) => info.port_path() == *port_path,
+            CableSelector::Serial(serial) => info.serial.as_ref() == Some(serial),
+        }
+    }
+
+    // USB addresses get reassigned every time a device is plugged in, so to find the same cable again after it's been
+    // unplugged we go by the port it was in instead
+    pub fn for_reconnect(&self, info: &CableInfo) -> CableSelector {
+        match self {
+            CableSelector::BusAddress(_, _) => CableSelector::PortPath(info.port_path()),
+            _ => self.clone(),
+        }
+    }
+}
+
 pub struct Cable {
     handle: DeviceHandle<GlobalContext>,
+    info: CableInfo,
     // the SilverLink has its own internal buffer, but our reads don't always align neatly with individual packets so we
     // need a buffer here too
     byte_buffer: Vec<u8>,
 
-    pub stat_bytes_read_overall: usize,
-
-    pub stat_malformed_reads: u64,
-    pub stat_overreads: u64,
+    stats: LinkStats,
 }
 impl Cable {
     pub fn new() -> Result<Cable, CableCreationError> {
+        Cable::open(&CableSelector::First)
+    }
+
+    pub fn open(selector: &CableSelector) -> Result<Cable, CableCreationError> {
         let devices = match rusb::devices() {
             Ok(devices) => devices,
             Err(e) => {
@@ -39,8 +117,8 @@ impl Cable {
             }
         };
 
-        let cable_handle = match get_link_cable(devices) {
-            Some(handle) => handle,
+        let (cable_handle, info) = match get_link_cable(devices, selector) {
+            Some(handle_and_info) => handle_and_info,
             None => {
                 return Err(CableCreationError::NoCableFound);
             }
@@ -63,37 +141,96 @@ impl Cable {
 
         Ok(Cable {
             handle: cable_handle,
+            info,
             byte_buffer: Vec::new(),
-            stat_bytes_read_overall: 0,
-            stat_malformed_reads: 0,
-            stat_overreads: 0,
+            stats: LinkStats::default(),
         })
     }
 
-    pub fn read_bytes(&mut self, bytes_expected: usize, timeout: Duration) -> Vec<u8> {
+    // every SilverLink currently plugged in, whether or not we'd be able to claim it
+    pub fn list() -> Result<Vec<CableInfo>, CableCreationError> {
+        let devices = match rusb::devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                return Err(CableCreationError::GetDevicesListFailed(e));
+            }
+        };
+
+        Ok(silverlink_devices(devices)
+            .iter()
+            .map(|device| cable_info(device, None))
+            .collect())
+    }
+
+    pub fn info(&self) -> &CableInfo {
+        &self.info
+    }
+
+    pub fn read_bytes(
+        &mut self,
+        bytes_expected: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, CableIoError> {
+        // rusb hands back whatever a timed out transfer did get as an `Ok`, only one that got nothing at all is a
+        // `Timeout`. So a frame split across a timeout is never lost, its start waits in `byte_buffer` for the rest
         while self.byte_buffer.len() < bytes_expected {
             let mut buf: [u8; 512] = [0; 512]; // the cable /advertises/ that the max packet size is 32 bytes. This is apparently a lie.
-            let read_size = self
-                .handle
-                .read_bulk(READ_ENDPOINT, &mut buf, timeout)
-                .unwrap();
+            let read_size = match self.handle.read_bulk(READ_ENDPOINT, &mut buf, timeout) {
+                Ok(read_size) => read_size,
+                Err(e) => {
+                    debug_eprintln!("slvnk: read failed. Reason: {e}");
+                    return Err(self.recover(READ_ENDPOINT, e));
+                }
+            };
 
-            self.stat_bytes_read_overall += read_size;
+            self.stats.bytes_read_overall += read_size;
+            if read_size > bytes_expected - self.byte_buffer.len() {
+                debug_eprintln!("slvnk: overread, got {read_size} bytes");
+                self.stats.overreads += 1;
+            }
 
             self.byte_buffer.extend_from_slice(&buf[0..read_size]);
         }
 
-        return self
+        Ok(self
             .byte_buffer
             .drain(0..bytes_expected)
-            .collect::<Vec<u8>>();
+            .collect::<Vec<u8>>())
     }
 
-    pub fn write_bytes(&mut self, bytes: &[u8], timeout: Duration) {
-        let _bytes_written = self
-            .handle
-            .write_bulk(WRITE_ENDPOINT, bytes, timeout)
-            .unwrap();
+    pub fn write_bytes(&mut self, bytes: &[u8], timeout: Duration) -> Result<(), CableIoError> {
+        let mut bytes_written = 0;
+        while bytes_written < bytes.len() {
+            bytes_written +=
+                match self
+                    .handle
+                    .write_bulk(WRITE_ENDPOINT, &bytes[bytes_written..], timeout)
+                {
+                    Ok(write_size) => write_size,
+                    Err(e) => {
+                        debug_eprintln!("slvnk: write failed. Reason: {e}");
+                        return Err(self.recover(WRITE_ENDPOINT, e));
+                    }
+                };
+        }
+
+        Ok(())
+    }
+
+    // a stalled endpoint stays stalled until someone clears it, so do that straight away. That way the caller can
+    // simply try again
+    fn recover(&mut self, endpoint: u8, e: rusb::Error) -> CableIoError {
+        let cable_error = CableIoError::from(e);
+
+        if cable_error == CableIoError::Stall {
+            #[allow(unused_variables)]
+            if let Err(e) = self.handle.clear_halt(endpoint) {
+                debug_eprintln!("slvnk: couldn't clear stall on {endpoint:#04x}. Reason: {e}");
+                return CableIoError::Other(e);
+            }
+        }
+
+        cable_error
     }
 
     pub fn release(&mut self) -> rusb::Result<()> {
@@ -101,7 +238,31 @@ impl Cable {
     }
 }
 
-fn get_link_cable(devices: DeviceList<GlobalContext>) -> Option<DeviceHandle<GlobalContext>> {
+impl LinkTransport for Cable {
+    fn read_bytes(
+        &mut self,
+        bytes_expected: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, LinkIoError> {
+        Ok(Cable::read_bytes(self, bytes_expected, timeout)?)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8], timeout: Duration) -> Result<(), LinkIoError> {
+        Ok(Cable::write_bytes(self, bytes, timeout)?)
+    }
+
+    fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut LinkStats {
+        &mut self.stats
+    }
+}
+
+fn silverlink_devices(devices: DeviceList<GlobalContext>) -> Vec<Device<GlobalContext>> {
+    let mut silverlinks = Vec::new();
+
     for device in devices.iter() {
         debug_eprintln!(
             "slvnk: Trying device {}:{}...",
@@ -125,6 +286,42 @@ fn get_link_cable(devices: DeviceList<GlobalContext>) -> Option<DeviceHandle<Glo
             continue;
         }
 
+        silverlinks.push(device);
+    }
+
+    silverlinks
+}
+
+// the serial number can only be read through an open handle, so if we don't have one we open the device just long
+// enough to ask
+fn cable_info(
+    device: &Device<GlobalContext>,
+    handle: Option<&DeviceHandle<GlobalContext>>,
+) -> CableInfo {
+    let serial = match device.device_descriptor() {
+        Ok(descriptor) if descriptor.serial_number_string_index().is_some() => match handle {
+            Some(handle) => handle.read_serial_number_string_ascii(&descriptor).ok(),
+            None => device
+                .open()
+                .and_then(|handle| handle.read_serial_number_string_ascii(&descriptor))
+                .ok(),
+        },
+        _ => None,
+    };
+
+    CableInfo {
+        bus_number: device.bus_number(),
+        address: device.address(),
+        port_numbers: device.port_numbers().unwrap_or_default(),
+        serial,
+    }
+}
+
+fn get_link_cable(
+    devices: DeviceList<GlobalContext>,
+    selector: &CableSelector,
+) -> Option<(DeviceHandle<GlobalContext>, CableInfo)> {
+    for device in silverlink_devices(devices) {
         let handle = match device.open() {
             Ok(handle) => handle,
             #[allow(unused_variables)]
@@ -134,15 +331,16 @@ fn get_link_cable(devices: DeviceList<GlobalContext>) -> Option<DeviceHandle<Glo
             }
         };
 
-        return Some(handle);
+        let info = cable_info(&device, Some(&handle));
+        if !selector.matches(&info) {
+            debug_eprintln!(
+                "slvnk: SilverLink cable at {} not selected, skipping.",
+                info.port_path()
+            );
+            continue;
+        }
+
+        return Some((handle, info));
     }
     None
 }
-
-// fn calculate_checksum(data: Vec<u8>) -> u16 {
-//     let mut checksum: u16 = 0;
-//     for byte in data {
-//         checksum = checksum.wrapping_add(byte as u16);
-//     }
-//     checksum
-// }
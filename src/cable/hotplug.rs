@@ -0,0 +1,133 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use debug_print::debug_eprintln;
+use rusb::{Device, GlobalContext, Hotplug, HotplugBuilder, Registration, UsbContext};
+
+use super::{SILVERLINK_PRODUCT_ID, TI_VENDOR_ID};
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum CableEvent {
+    Arrived,
+    Left,
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum CableWatcherCreationError {
+    HotplugUnsupported,
+    RegisterFailed(rusb::Error),
+}
+
+struct CableHotplug {
+    sender: Sender<CableEvent>,
+}
+impl Hotplug<GlobalContext> for CableHotplug {
+    #[allow(unused_variables)]
+    fn device_arrived(&mut self, device: Device<GlobalContext>) {
+        debug_eprintln!(
+            "hotplug: SilverLink arrived at {}:{}",
+            device.bus_number(),
+            device.address()
+        );
+        let _ = self.sender.send(CableEvent::Arrived);
+    }
+
+    #[allow(unused_variables)]
+    fn device_left(&mut self, device: Device<GlobalContext>) {
+        debug_eprintln!(
+            "hotplug: SilverLink left {}:{}",
+            device.bus_number(),
+            device.address()
+        );
+        let _ = self.sender.send(CableEvent::Left);
+    }
+}
+
+// keeps an eye on SilverLink cables coming and going. libusb only calls hotplug callbacks from inside its event
+// handling, so we keep a thread around doing nothing but that
+pub struct CableWatcher {
+    events: Receiver<CableEvent>,
+    stop: Arc<AtomicBool>,
+    event_thread: Option<JoinHandle<()>>,
+    _registration: Registration<GlobalContext>,
+}
+impl CableWatcher {
+    pub fn new() -> Result<CableWatcher, CableWatcherCreationError> {
+        if !rusb::has_hotplug() {
+            return Err(CableWatcherCreationError::HotplugUnsupported);
+        }
+
+        let (sender, events) = mpsc::channel();
+
+        let registration = match HotplugBuilder::new()
+            .vendor_id(TI_VENDOR_ID)
+            .product_id(SILVERLINK_PRODUCT_ID)
+            .enumerate(false)
+            .register(GlobalContext::default(), Box::new(CableHotplug { sender }))
+        {
+            Ok(registration) => registration,
+            Err(e) => {
+                return Err(CableWatcherCreationError::RegisterFailed(e));
+            }
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let event_thread = thread::spawn(move || {
+            while !thread_stop.load(Ordering::Relaxed) {
+                #[allow(unused_variables)]
+                if let Err(e) =
+                    GlobalContext::default().handle_events(Some(Duration::from_millis(250)))
+                {
+                    debug_eprintln!("hotplug: handling events failed. Reason: {e}");
+                }
+            }
+        });
+
+        Ok(CableWatcher {
+            events,
+            stop,
+            event_thread: Some(event_thread),
+            _registration: registration,
+        })
+    }
+
+    // whether a cable has been unplugged since we last asked. Everything queued gets gone through, so there's no stale
+    // arrival left behind for `wait_for_arrival` to find
+    pub fn cable_left(&self) -> bool {
+        self.events
+            .try_iter()
+            .filter(|event| *event == CableEvent::Left)
+            .count()
+            > 0
+    }
+
+    // whether a cable turned up within `timeout`
+    pub fn wait_for_arrival(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while let Ok(event) = self
+            .events
+            .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        {
+            if event == CableEvent::Arrived {
+                return true;
+            }
+        }
+        false
+    }
+}
+impl Drop for CableWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(event_thread) = self.event_thread.take() {
+            let _ = event_thread.join();
+        }
+    }
+}
@@ -5,35 +5,186 @@ use ti83p::TI83Plus;
 use ti89::TI89;
 use ti92p::TI92Plus;
 
-use crate::{cable::Cable, keyboard::CalcKey};
+use crate::{
+    keyboard::CalcKey,
+    link::{LinkIoError, LinkStats, LinkTransport},
+    shutdown,
+};
 
 pub mod ti92p;
 pub mod ti89;
 pub mod ti83p;
 
-pub trait CalcHandle {
-    fn get_keys(&mut self, cable: &mut Cable) -> Vec<(CalcKey, bool)>;
+// the machine IDs soyuz reports during the handshake. These are i68's own, not TI's link protocol IDs
+pub const MACHINE_ID_TI92P: u8 = 192;
+pub const MACHINE_ID_TI89: u8 = 89;
+pub const MACHINE_ID_TI83P: u8 = 183;
+
+// [quit key row, quit key column, report format, scan rate, flags], see `SessionConfig`
+pub const SESSION_CONFIG_LEN: usize = 5;
+
+pub trait CalcHandle: Send {
+    fn get_keys(
+        &mut self,
+        link: &mut dyn LinkTransport,
+        timeout: Duration,
+    ) -> Result<Vec<(CalcKey, bool)>, LinkIoError>;
 }
 
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum HandshakeError {
     VersionMismatch(u8, u8, u8),
     UnknownMachineId(u8),
+    // the quit key asked for isn't on this model
+    NoSuchKey(CalcKey),
+    // soyuz answered the configuration with something that wasn't asked for or doesn't make sense
+    BadConfig([u8; SESSION_CONFIG_LEN]),
+    LinkError(LinkIoError),
+    // shutting down while still waiting for soyuz to say it's ready
+    Interrupted,
     OtherError,
 }
+impl From<LinkIoError> for HandshakeError {
+    fn from(e: LinkIoError) -> Self {
+        HandshakeError::LinkError(e)
+    }
+}
+
+// optional features, for `SessionConfig::flags`
+
+// every report comes wrapped in a frame with a checksum, so apollo can find its way back into step after a byte goes
+// missing or gets mangled. See `encode_frame`
+pub const FLAG_FRAMED: u8 = 0x01;
+
+// when there's nothing else to send, soyuz sends a report anyway every `HEARTBEAT_INTERVAL`, so apollo can tell a quiet
+// calculator from one that's gone. For full matrices that's the same matrix again, for deltas it's one with no changes
+pub const FLAG_HEARTBEAT: u8 = 0x02;
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+// soyuz takes `SHUTDOWN_REQUEST` from apollo mid-session as its cue to go back to the home screen, same as if the quit
+// key was pressed
+pub const FLAG_SHUTDOWN: u8 = 0x04;
+pub const SHUTDOWN_REQUEST: u8 = 0x51;
+
+// how often the wait for soyuz to be started checks whether to give up on it
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// the first byte of every frame
+pub const FRAME_START: u8 = 0xA5;
+
+// the first byte of a delta report that's a whole key matrix rather than a count of changes
+pub const DELTA_KEYFRAME: u8 = 0xFF;
+
+// how soyuz sends the key matrix
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum ReportFormat {
+    // the whole matrix, every scan
+    FullMatrix,
+    // only what changed since the last report: [count, change records...], see `delta_record`. Every so often, and
+    // always first, there's a keyframe instead: [`DELTA_KEYFRAME`, the whole matrix]
+    Delta,
+}
+impl ReportFormat {
+    fn from_byte(byte: u8) -> Option<ReportFormat> {
+        match byte {
+            0 => Some(ReportFormat::FullMatrix),
+            1 => Some(ReportFormat::Delta),
+            _ => None,
+        }
+    }
+
+    fn to_byte(self) -> u8 {
+        match self {
+            ReportFormat::FullMatrix => 0,
+            ReportFormat::Delta => 1,
+        }
+    }
+}
+
+// what apollo asks of soyuz after the version check. Soyuz answers in the same form with what it'll actually do, which
+// can be less than what was asked for but never more
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct SessionConfig {
+    // ends the session when pressed, and never makes it to the virtual keyboard
+    pub quit_key: CalcKey,
+    pub report_format: ReportFormat,
+    // key matrix scans a second
+    pub scan_rate: u8,
+    // optional features, one `FLAG_*` bit each
+    pub flags: u8,
+}
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            quit_key: CalcKey::ON,
+            report_format: ReportFormat::Delta,
+            scan_rate: 20,
+            flags: FLAG_FRAMED | FLAG_HEARTBEAT | FLAG_SHUTDOWN,
+        }
+    }
+}
+impl SessionConfig {
+    // the quit key goes by where it is in the key matrix, since that's all soyuz knows about
+    pub fn encode(&self, machine_id: u8) -> Option<[u8; SESSION_CONFIG_LEN]> {
+        let (_, layout) = key_layout(machine_id)?;
+        let ((row, col), _) = layout.iter().find(|(_, key)| *key == self.quit_key)?;
+
+        Some([
+            *row as u8,
+            *col,
+            self.report_format.to_byte(),
+            self.scan_rate,
+            self.flags,
+        ])
+    }
+
+    pub fn decode(machine_id: u8, bytes: &[u8]) -> Option<SessionConfig> {
+        let (_, layout) = key_layout(machine_id)?;
+        let [row, col, report_format, scan_rate, flags] = *bytes else {
+            return None;
+        };
+        let (_, quit_key) = layout
+            .iter()
+            .find(|((key_row, key_col), _)| *key_row == row as usize && *key_col == col)?;
+
+        Some(SessionConfig {
+            quit_key: *quit_key,
+            report_format: ReportFormat::from_byte(report_format)?,
+            scan_rate,
+            flags,
+        })
+    }
+}
 
 pub struct I68MetaInfo {
     pub soyuz_ver: (u8, u8, u8),
     pub machine_id: u8,
+    // what was agreed on with soyuz
+    pub config: SessionConfig,
     pub calc_handle: Box<dyn CalcHandle>,
 }
 impl I68MetaInfo {
-    pub fn handshake(cable: &mut Cable) -> Result<I68MetaInfo, HandshakeError> {
+    pub fn handshake(link: &mut dyn LinkTransport) -> Result<I68MetaInfo, HandshakeError> {
+        I68MetaInfo::handshake_with(link, &SessionConfig::default())
+    }
+
+    pub fn handshake_with(
+        link: &mut dyn LinkTransport,
+        requested: &SessionConfig,
+    ) -> Result<I68MetaInfo, HandshakeError> {
         let (apollo_ver_major, apollo_ver_minor, apollo_ver_patch) = apollo_version();
 
-        // ready?
+        // ready? This is the part that waits on the user, for as long as it takes
 
-        let ready_byte = cable.read_bytes(1, Duration::from_secs(0));
+        let ready_byte = loop {
+            match link.read_bytes(1, READY_POLL_INTERVAL) {
+                Err(LinkIoError::Timeout) if shutdown::requested() => {
+                    return Err(HandshakeError::Interrupted);
+                }
+                Err(LinkIoError::Timeout) => continue,
+                result => break result?,
+            }
+        };
         debug_eprintln!("ready_byte: {:?}", ready_byte);
         if ready_byte[0] != 0x50 {
             return Err(HandshakeError::OtherError);
@@ -41,12 +192,12 @@ impl I68MetaInfo {
 
         // version check
 
-        let soyuz_ver = cable.read_bytes(3, Duration::from_secs(0));
+        let soyuz_ver = link.read_bytes(3, Duration::from_secs(0))?;
 
         debug_eprintln!("soyuz ver: {:?}", soyuz_ver);
 
         let apollo_ver: [u8; 3] = [apollo_ver_major, apollo_ver_minor, apollo_ver_patch];
-        cable.write_bytes(&apollo_ver, Duration::from_secs(0));
+        link.write_bytes(&apollo_ver, Duration::from_secs(0))?;
 
         let soyuz_ver_major = soyuz_ver[0];
         let soyuz_ver_minor = soyuz_ver[1];
@@ -62,24 +213,341 @@ impl I68MetaInfo {
 
         // machine id
 
-        let machine_id = cable.read_bytes(1, Duration::from_secs(0))[0];
+        let machine_id = link.read_bytes(1, Duration::from_secs(0))?[0];
         debug_eprintln!("machine id: {machine_id}");
 
-        let calc_handle: Box<dyn CalcHandle> = match machine_id {
-            192 => Box::new(TI92Plus::new()),
-            089 => Box::new(TI89::new()),
-            183 => Box::new(TI83Plus::new()),
+        if key_layout(machine_id).is_none() {
+            return Err(HandshakeError::UnknownMachineId(machine_id));
+        }
+
+        // configuration
+
+        let request = requested
+            .encode(machine_id)
+            .ok_or(HandshakeError::NoSuchKey(requested.quit_key))?;
+        link.write_bytes(&request, Duration::from_secs(0))?;
+
+        let answer = link.read_bytes(SESSION_CONFIG_LEN, Duration::from_secs(0))?;
+        debug_eprintln!("config: asked for {request:?}, got {answer:?}");
+
+        let mut answer_bytes = [0; SESSION_CONFIG_LEN];
+        answer_bytes.copy_from_slice(&answer);
+        let config = match SessionConfig::decode(machine_id, &answer) {
+            Some(config)
+                if config.flags & !requested.flags == 0
+                    && config.scan_rate <= requested.scan_rate
+                    // every soyuz can fall back on full matrices
+                    && (config.report_format == requested.report_format
+                        || config.report_format == ReportFormat::FullMatrix) =>
+            {
+                config
+            }
             _ => {
-                return Err(HandshakeError::UnknownMachineId(machine_id));
+                return Err(HandshakeError::BadConfig(answer_bytes));
             }
         };
 
+        let calc_handle: Box<dyn CalcHandle> = match machine_id {
+            MACHINE_ID_TI92P => Box::new(TI92Plus::with_config(&config)),
+            MACHINE_ID_TI89 => Box::new(TI89::with_config(&config)),
+            _ => Box::new(TI83Plus::with_config(&config)),
+        };
+
         Ok(I68MetaInfo {
             soyuz_ver: (soyuz_ver_major, soyuz_ver_minor, soyuz_ver_patch),
             machine_id,
+            config,
             calc_handle,
         })
     }
+
+    // has soyuz exit, if it agreed to take being told to. Whether it did is the answer
+    pub fn request_exit(&self, link: &mut dyn LinkTransport) -> Result<bool, LinkIoError> {
+        if self.config.flags & FLAG_SHUTDOWN == 0 {
+            return Ok(false);
+        }
+
+        // on the way out, so no waiting around for a calculator that's not listening
+        link.write_bytes(&[SHUTDOWN_REQUEST], Duration::from_secs(1))?;
+        Ok(true)
+    }
+}
+
+pub fn model_name(machine_id: u8) -> Option<&'static str> {
+    match machine_id {
+        MACHINE_ID_TI92P => Some("TI-92 Plus"),
+        MACHINE_ID_TI89 => Some("TI-89"),
+        MACHINE_ID_TI83P => Some("TI-83 Plus"),
+        _ => None,
+    }
+}
+
+// ((row, column), key) for every key a model's matrix can report
+pub type KeyLayout = &'static [((usize, u8), CalcKey)];
+
+// frame length and matrix layout for a given machine ID
+pub fn key_layout(machine_id: u8) -> Option<(usize, KeyLayout)> {
+    match machine_id {
+        MACHINE_ID_TI92P => Some((ti92p::KEY_MATRIX_LEN, &ti92p::KEY_TO_KEY_MAP)),
+        MACHINE_ID_TI89 => Some((ti89::KEY_MATRIX_LEN, &ti89::KEY_TO_KEY_MAP)),
+        MACHINE_ID_TI83P => Some((ti83p::KEY_MATRIX_LEN, &ti83p::KEY_TO_KEY_MAP)),
+        _ => None,
+    }
+}
+
+// bookkeeping for each key matrix frame that comes off the link. No real key sits on a bit outside the layout, so one
+// being set means the frame got mangled on the way
+pub fn record_frame(stats: &mut LinkStats, layout: KeyLayout, key_matrix: &[u8]) {
+    stats.frames_read += 1;
+
+    let mut key_bits = vec![0u8; key_matrix.len()];
+    for ((row, col), _) in layout {
+        key_bits[*row] |= 1 << col;
+    }
+
+    if key_matrix
+        .iter()
+        .zip(key_bits)
+        .any(|(row, key_bits)| row & !key_bits != 0)
+    {
+        debug_eprintln!("malformed key matrix: {:?}", key_matrix);
+        stats.malformed_reads += 1;
+    }
+}
+
+// reads reports off the link in whatever form was agreed on in the handshake
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub(crate) struct ReportReader {
+    report_format: ReportFormat,
+    framed: bool,
+    // bytes read while looking for the next frame that haven't been used yet
+    frame_buffer: Vec<u8>,
+    // the sequence number the next frame should have, once there's been one
+    next_seq: Option<u8>,
+    // skipping over garbage, which only counts as malformed once however long it goes on for
+    resyncing: bool,
+    // whether any frames went missing since the last report was applied
+    missed_frames: bool,
+    // an unframed delta report's change count, held on to if the read for its changes times out
+    change_count: Option<u8>,
+}
+impl ReportReader {
+    pub(crate) fn new(config: &SessionConfig) -> ReportReader {
+        ReportReader {
+            report_format: config.report_format,
+            framed: config.flags & FLAG_FRAMED != 0,
+            frame_buffer: Vec::new(),
+            next_seq: None,
+            resyncing: false,
+            missed_frames: false,
+            change_count: None,
+        }
+    }
+
+    // brings `key_matrix` up to date with the next report off the link
+    pub(crate) fn read(
+        &mut self,
+        link: &mut dyn LinkTransport,
+        timeout: Duration,
+        layout: KeyLayout,
+        key_matrix: &mut [u8],
+    ) -> Result<(), LinkIoError> {
+        let report = match (self.framed, self.report_format) {
+            (true, ReportFormat::FullMatrix) => self.read_frame(link, timeout, key_matrix.len())?,
+            // every key in the matrix changing at once, which is longer than a keyframe
+            (true, ReportFormat::Delta) => self.read_frame(link, timeout, 1 + 8 * key_matrix.len())?,
+            (false, ReportFormat::FullMatrix) => link.read_bytes(key_matrix.len(), timeout)?,
+            (false, ReportFormat::Delta) => {
+                let change_count = match self.change_count {
+                    Some(change_count) => change_count,
+                    None => link.read_bytes(1, timeout)?[0],
+                };
+                self.change_count = Some(change_count);
+                let report_len = match change_count {
+                    DELTA_KEYFRAME => key_matrix.len(),
+                    _ => change_count as usize,
+                };
+                let changes = link.read_bytes(report_len, timeout)?;
+                self.change_count = None;
+                [vec![change_count], changes].concat()
+            }
+        };
+
+        // a missing delta might've been a key being let go, which no later delta is going to repeat. Better to let go
+        // of everything than leave a key stuck down, and the next keyframe puts back whatever's really held
+        if self.missed_frames && self.report_format == ReportFormat::Delta {
+            key_matrix.fill(0);
+        }
+        self.missed_frames = false;
+
+        apply_report(
+            link.stats_mut(),
+            self.report_format,
+            layout,
+            &report,
+            key_matrix,
+        );
+        Ok(())
+    }
+
+    // the payload of the next frame with a good checksum. A bad one only gets its start byte thrown away, so the search
+    // for the real next frame picks up straight after it. So does one claiming to be longer than `max_payload_len`,
+    // rather than holding everything up waiting on bytes that'll never come
+    fn read_frame(
+        &mut self,
+        link: &mut dyn LinkTransport,
+        timeout: Duration,
+        max_payload_len: usize,
+    ) -> Result<Vec<u8>, LinkIoError> {
+        loop {
+            match self
+                .frame_buffer
+                .iter()
+                .position(|byte| *byte == FRAME_START)
+            {
+                Some(0) => {}
+                Some(start) => {
+                    self.skip(link.stats_mut(), start);
+                }
+                None => {
+                    let garbage_len = self.frame_buffer.len();
+                    self.skip(link.stats_mut(), garbage_len);
+                    self.frame_buffer.extend(link.read_bytes(1, timeout)?);
+                    continue;
+                }
+            }
+
+            // [FRAME_START, sequence number, payload length, payload, checksum]
+            let frame_len = match self.frame_buffer.get(2) {
+                Some(payload_len) if *payload_len as usize > max_payload_len => {
+                    debug_eprintln!("frame too long to be a report: {payload_len} byte(s)");
+                    self.skip(link.stats_mut(), 1);
+                    continue;
+                }
+                Some(payload_len) => 4 + *payload_len as usize,
+                None => 3,
+            };
+            if self.frame_buffer.len() < frame_len {
+                let missing = frame_len - self.frame_buffer.len();
+                self.frame_buffer.extend(link.read_bytes(missing, timeout)?);
+                continue;
+            }
+
+            let frame = &self.frame_buffer[..frame_len];
+            if frame_checksum(&frame[1..frame_len - 1]) != frame[frame_len - 1] {
+                debug_eprintln!("frame failed its checksum: {frame:?}");
+                self.skip(link.stats_mut(), 1);
+                continue;
+            }
+
+            // frames can go missing whole, without a damaged byte to show for it. If there was one, it's already been
+            // counted
+            let seq = frame[1];
+            if let Some(next_seq) = self.next_seq.filter(|next_seq| *next_seq != seq) {
+                debug_eprintln!("{} frame(s) went missing", seq.wrapping_sub(next_seq));
+                if !self.resyncing {
+                    link.stats_mut().malformed_reads += 1;
+                }
+                self.missed_frames = true;
+            }
+            self.next_seq = Some(seq.wrapping_add(1));
+            self.resyncing = false;
+
+            let payload = frame[3..frame_len - 1].to_vec();
+            self.frame_buffer.drain(..frame_len);
+            return Ok(payload);
+        }
+    }
+
+    fn skip(&mut self, stats: &mut LinkStats, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        debug_eprintln!("skipping {len} byte(s) looking for a frame");
+        if !self.resyncing {
+            stats.malformed_reads += 1;
+            self.resyncing = true;
+        }
+        self.frame_buffer.drain(..len);
+    }
+}
+
+// a report in `report_format`, which has to be the right length for it to count
+fn apply_report(
+    stats: &mut LinkStats,
+    report_format: ReportFormat,
+    layout: KeyLayout,
+    report: &[u8],
+    key_matrix: &mut [u8],
+) {
+    let records = match (report_format, report.split_first()) {
+        (ReportFormat::FullMatrix, _) if report.len() == key_matrix.len() => {
+            key_matrix.copy_from_slice(report);
+            record_frame(stats, layout, key_matrix);
+            return;
+        }
+        (ReportFormat::Delta, Some((&DELTA_KEYFRAME, matrix)))
+            if matrix.len() == key_matrix.len() =>
+        {
+            key_matrix.copy_from_slice(matrix);
+            record_frame(stats, layout, key_matrix);
+            return;
+        }
+        (ReportFormat::Delta, Some((change_count, records)))
+            if *change_count as usize == records.len() =>
+        {
+            records
+        }
+        _ => {
+            debug_eprintln!("report is the wrong length: {report:?}");
+            stats.frames_read += 1;
+            stats.malformed_reads += 1;
+            return;
+        }
+    };
+
+    stats.frames_read += 1;
+
+    let mut malformed = false;
+    for record in records {
+        let row = (record >> 4) as usize;
+        let col = (record >> 1) & 0x07;
+        let pressed = record & 1 != 0;
+        if !layout.iter().any(|(position, _)| *position == (row, col)) {
+            debug_eprintln!("malformed change record: {record:#04x}");
+            malformed = true;
+            continue;
+        }
+
+        if pressed {
+            key_matrix[row] |= 1 << col;
+        } else {
+            key_matrix[row] &= !(1 << col);
+        }
+    }
+    if malformed {
+        stats.malformed_reads += 1;
+    }
+}
+
+// a report wrapped up for sending with `FLAG_FRAMED`
+pub fn encode_frame(seq: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![FRAME_START, seq, payload.len() as u8];
+    frame.extend_from_slice(payload);
+    frame.push(frame_checksum(&frame[1..]));
+    frame
+}
+
+// everything between the start byte and the checksum, added up
+fn frame_checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+// [row (4 bits), column (3 bits), pressed (1 bit)], which can never come out as `DELTA_KEYFRAME` since there are at
+// most 10 rows
+pub fn delta_record(row: usize, col: u8, pressed: bool) -> u8 {
+    ((row as u8) << 4) | (col << 1) | pressed as u8
 }
 
 pub fn apollo_version() -> (u8, u8, u8) {
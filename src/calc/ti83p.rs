@@ -1,8 +1,11 @@
 use std::time::Duration;
 
-use crate::{cable::Cable, keyboard::CalcKey};
+use crate::{
+    keyboard::CalcKey,
+    link::{LinkIoError, LinkTransport},
+};
 
-use super::CalcHandle;
+use super::{CalcHandle, ReportFormat, ReportReader, SessionConfig};
 
 pub const KEY_MATRIX_LEN: usize = 7;
 
@@ -59,16 +62,21 @@ pub const KEY_TO_KEY_MAP: [((usize, u8), CalcKey); 50] = [
     ((6, 0), CalcKey::F5),
 ];
 
-#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+#[derive(Eq, PartialEq, Debug, Clone)]
 pub struct TI83Plus {
     key_matrix: [u8; KEY_MATRIX_LEN],
     prev_key_matrix: [u8; KEY_MATRIX_LEN],
+    reports: ReportReader,
 }
 impl CalcHandle for TI83Plus {
-    fn get_keys(&mut self, cable: &mut Cable) -> Vec<(crate::keyboard::CalcKey, bool)> {
+    fn get_keys(
+        &mut self,
+        link: &mut dyn LinkTransport,
+        timeout: Duration,
+    ) -> Result<Vec<(crate::keyboard::CalcKey, bool)>, LinkIoError> {
         self.prev_key_matrix.clone_from_slice(&self.key_matrix);
-        self.key_matrix
-            .copy_from_slice(&cable.read_bytes(KEY_MATRIX_LEN, Duration::from_secs(0)));
+        self.reports
+            .read(link, timeout, &KEY_TO_KEY_MAP, &mut self.key_matrix)?;
 
         let mut keys = Vec::new();
 
@@ -83,14 +91,25 @@ impl CalcHandle for TI83Plus {
             }
         }
 
-        keys
+        Ok(keys)
     }
 }
 impl TI83Plus {
     pub fn new() -> TI83Plus {
+        // full matrices, unframed, which is all there was before the handshake had a say
+        TI83Plus::with_config(&SessionConfig {
+            report_format: ReportFormat::FullMatrix,
+            flags: 0,
+            ..SessionConfig::default()
+        })
+    }
+
+    // reading reports however the handshake said to
+    pub fn with_config(config: &SessionConfig) -> TI83Plus {
         TI83Plus {
             key_matrix: [0; KEY_MATRIX_LEN],
             prev_key_matrix: [0; KEY_MATRIX_LEN],
+            reports: ReportReader::new(config),
         }
     }
 }
@@ -0,0 +1,572 @@
+use crate::{
+    calc::{MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P},
+    link::packet::checksum,
+};
+
+// the files TI's own software uses to store variables on a computer: .89* for the TI-89, .9x* for the TI-92 Plus and
+// .8x* for the TI-83 Plus. Group files (.89g, .9xg, .8xg) are the same format with more than one variable in them
+
+const TI89_SIGNATURE: &[u8; 8] = b"**TI89**";
+const TI92P_SIGNATURE: &[u8; 8] = b"**TI92P*";
+const TI83P_SIGNATURE: &[u8; 8] = b"**TI83F*";
+
+// 89/92+ files are a fixed header, a table of 16 byte entries, the file size and A5 5A, then each variable
+const TI89_HEADER_LEN: usize = 0x3C;
+const TI89_ENTRY_LEN: usize = 16;
+// four bytes of zeros before each variable's data and a checksum after it
+const TI89_VAR_OVERHEAD: usize = 4 + 2;
+// a table entry that starts a folder rather than being a variable
+const TI89_FOLDER_TYPE: u8 = 0x1F;
+
+// where the data section starts in an 83+ file
+const TI83P_DATA_OFFSET: usize = 0x37;
+// the length of each variable's header, not counting the length itself. Flash-era files add the version and flag
+const TI83P_SHORT_HEADER_LEN: u16 = 0x0B;
+const TI83P_HEADER_LEN: u16 = 0x0D;
+
+// variable attributes, 89/92+ then 83+
+pub const TI89_ATTR_LOCKED: u8 = 0x01;
+pub const TI89_ATTR_ARCHIVED: u8 = 0x03;
+pub const TI83P_ATTR_ARCHIVED: u8 = 0x80;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum FormatError {
+    UnknownSignature,
+    Truncated,
+    // (stored, calculated)
+    BadChecksum(u16, u16),
+    // the file holds more than the one variable
+    GroupFile,
+}
+
+// one variable, in the form it goes over the link. For the 89/92+ that's with its two byte (big endian!) length on the
+// front, for the 83+ the length is only part of the data for the types that carry one
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct TiVar {
+    // always empty on the 83+, which has no folders
+    pub folder: String,
+    pub name: String,
+    pub var_type: u8,
+    pub attr: u8,
+    // 83+ only, zero elsewhere
+    pub version: u8,
+    pub data: Vec<u8>,
+}
+
+// the bytes a file has room for that don't mean anything to the calculator, kept from a file that's been read in so it
+// can be written back out exactly. Empty for files made here, or read ones that only have what TiLP would've written
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct FilePadding {
+    // straight after the signature. 01 00 on the 89/92+, 1A 0A 00 on the 83+
+    pub after_signature: Vec<u8>,
+    // the folder (89/92+) and comment fields as they were, whatever was past the end of the text included
+    pub folder: Vec<u8>,
+    pub comment: Vec<u8>,
+    // 89/92+ only, one per folder entry in the table: its name field and the byte after its type
+    pub folder_entries: Vec<(Vec<u8>, u8)>,
+    // one per variable, in the same order as `VarFile::vars`
+    pub vars: Vec<VarPadding>,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct VarPadding {
+    pub name: Vec<u8>,
+    // 89/92+ only. The last two bytes of the variable's table entry, and the four in front of its data
+    pub entry_tail: [u8; 2],
+    pub data_prefix: [u8; 4],
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct VarFile {
+    // which model the file's for, as one of the i68 machine IDs
+    pub machine_id: u8,
+    // the folder in the 89/92+ header. Variables are in this one unless the file says otherwise. Empty on the 83+
+    pub folder: String,
+    pub comment: String,
+    pub vars: Vec<TiVar>,
+    // 83+ only. Whether the variables have the older header without a version or flag, as written by pre-flash software
+    pub short_headers: bool,
+    pub padding: FilePadding,
+}
+impl VarFile {
+    pub fn single(machine_id: u8, comment: &str, var: TiVar) -> VarFile {
+        VarFile {
+            machine_id,
+            folder: var.folder.clone(),
+            comment: comment.to_string(),
+            vars: vec![var],
+            short_headers: false,
+            padding: FilePadding::default(),
+        }
+    }
+
+    // the header folder is the first variable's, or main if there aren't any
+    pub fn group(machine_id: u8, comment: &str, vars: Vec<TiVar>) -> VarFile {
+        let folder = match (machine_id, vars.first()) {
+            (MACHINE_ID_TI83P, _) => String::new(),
+            (_, Some(var)) => var.folder.clone(),
+            (_, None) => "main".to_string(),
+        };
+
+        VarFile {
+            machine_id,
+            folder,
+            comment: comment.to_string(),
+            vars,
+            short_headers: false,
+            padding: FilePadding::default(),
+        }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<VarFile, FormatError> {
+        if bytes.len() < 8 {
+            return Err(FormatError::Truncated);
+        }
+
+        let mut file = match &bytes[0..8] {
+            signature if signature == TI89_SIGNATURE => parse_ti89(bytes, MACHINE_ID_TI89)?,
+            signature if signature == TI92P_SIGNATURE => parse_ti89(bytes, MACHINE_ID_TI92P)?,
+            signature if signature == TI83P_SIGNATURE => parse_ti83p(bytes)?,
+            _ => {
+                return Err(FormatError::UnknownSignature);
+            }
+        };
+
+        // no need to carry around padding that'd be written the same without it
+        let padding = std::mem::take(&mut file.padding);
+        if file.to_bytes() != bytes {
+            file.padding = padding;
+        }
+
+        Ok(file)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self.machine_id {
+            MACHINE_ID_TI83P => write_ti83p(self),
+            _ => write_ti89(self),
+        }
+    }
+
+    // the one variable in the file, for when a group won't do
+    pub fn into_single(mut self) -> Result<TiVar, FormatError> {
+        match self.vars.len() {
+            1 => Ok(self.vars.remove(0)),
+            0 => Err(FormatError::Truncated),
+            _ => Err(FormatError::GroupFile),
+        }
+    }
+
+    // what TI's software would call this file
+    pub fn file_name(&self) -> String {
+        match self.vars.as_slice() {
+            [var] => format!(
+                "{}.{}",
+                var.name,
+                extension(self.machine_id, var.var_type).unwrap_or("var".to_string())
+            ),
+            _ => format!(
+                "group.{}",
+                extension_prefix(self.machine_id)
+                    .map(|prefix| format!("{prefix}g"))
+                    .unwrap_or("var".to_string())
+            ),
+        }
+    }
+}
+
+// TI's names for each variable type, along with the last letter of the file extension
+const TI89_TYPES: &[(u8, &str, char)] = &[
+    (0x00, "EXPR", 'e'),
+    (0x04, "LIST", 'l'),
+    (0x06, "MAT", 'm'),
+    (0x0A, "DATA", 'c'),
+    (0x0B, "TEXT", 't'),
+    (0x0C, "STR", 's'),
+    (0x0D, "GDB", 'd'),
+    (0x0E, "FIG", 'a'),
+    (0x10, "PIC", 'i'),
+    (0x12, "PRGM", 'p'),
+    (0x13, "FUNC", 'f'),
+    (0x14, "MAC", 'x'),
+    (0x1C, "OTH", 'y'),
+    (0x21, "ASM", 'z'),
+    (0x24, "APP", 'k'),
+];
+const TI83P_TYPES: &[(u8, &str, char)] = &[
+    (0x00, "REAL", 'n'),
+    (0x01, "LIST", 'l'),
+    (0x02, "MATRX", 'm'),
+    (0x03, "EQU", 'y'),
+    (0x04, "STRNG", 's'),
+    (0x05, "PRGM", 'p'),
+    (0x06, "PPRGM", 'p'),
+    (0x07, "PIC", 'i'),
+    (0x08, "GDB", 'd'),
+    (0x0B, "WINDW", 'w'),
+    (0x0C, "CPLX", 'c'),
+    (0x0D, "CLIST", 'l'),
+    (0x0F, "ZSTO", 'z'),
+    (0x11, "TABLE", 't'),
+    (0x15, "APPV", 'v'),
+    (0x24, "APP", 'k'),
+];
+
+fn type_entry(machine_id: u8, var_type: u8) -> Option<(&'static str, char)> {
+    let types = match machine_id {
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => TI89_TYPES,
+        MACHINE_ID_TI83P => TI83P_TYPES,
+        _ => return None,
+    };
+
+    types
+        .iter()
+        .find(|(id, _, _)| *id == var_type)
+        .map(|(_, name, letter)| (*name, *letter))
+}
+
+pub fn type_name(machine_id: u8, var_type: u8) -> Option<&'static str> {
+    type_entry(machine_id, var_type).map(|(name, _)| name)
+}
+
+// e.g. "89p" for a TI-89 program
+pub fn extension(machine_id: u8, var_type: u8) -> Option<String> {
+    let prefix = extension_prefix(machine_id)?;
+
+    type_entry(machine_id, var_type).map(|(_, letter)| format!("{prefix}{letter}"))
+}
+
+fn extension_prefix(machine_id: u8) -> Option<&'static str> {
+    match machine_id {
+        MACHINE_ID_TI89 => Some("89"),
+        MACHINE_ID_TI92P => Some("9x"),
+        MACHINE_ID_TI83P => Some("8x"),
+        _ => None,
+    }
+}
+
+pub fn is_archived(machine_id: u8, attr: u8) -> bool {
+    match machine_id {
+        MACHINE_ID_TI83P => attr & TI83P_ATTR_ARCHIVED != 0,
+        _ => attr == TI89_ATTR_ARCHIVED,
+    }
+}
+
+// the 89 and 92+ formats only differ in their signature
+fn parse_ti89(bytes: &[u8], machine_id: u8) -> Result<VarFile, FormatError> {
+    if bytes.len() < TI89_HEADER_LEN {
+        return Err(FormatError::Truncated);
+    }
+
+    let entry_count = u16::from_le_bytes([bytes[0x3A], bytes[0x3B]]) as usize;
+    let entries_end = TI89_HEADER_LEN + entry_count * TI89_ENTRY_LEN;
+    if bytes.len() < entries_end + 6 {
+        return Err(FormatError::Truncated);
+    }
+
+    let file_size =
+        u32::from_le_bytes(bytes[entries_end..entries_end + 4].try_into().unwrap()) as usize;
+    if bytes.len() < file_size || file_size < entries_end + 6 {
+        return Err(FormatError::Truncated);
+    }
+
+    let folder = padded_string(&bytes[0x0A..0x12]);
+    let mut padding = FilePadding {
+        after_signature: bytes[0x08..0x0A].to_vec(),
+        folder: bytes[0x0A..0x12].to_vec(),
+        comment: bytes[0x12..0x3A].to_vec(),
+        ..FilePadding::default()
+    };
+
+    // [offset (LE u32), name, type, attribute, two bytes that only folders use, for how many variables they hold]
+    let entries = bytes[TI89_HEADER_LEN..entries_end].chunks(TI89_ENTRY_LEN);
+
+    // variables are in whatever folder the last folder entry named, or the header's if there wasn't one
+    let mut current_folder = folder.clone();
+    let mut headers = Vec::new();
+    for entry in entries {
+        let offset = u32::from_le_bytes(entry[0..4].try_into().unwrap()) as usize;
+        let name = padded_string(&entry[4..12]);
+
+        match entry[12] {
+            TI89_FOLDER_TYPE => {
+                current_folder = name;
+                padding
+                    .folder_entries
+                    .push((entry[4..12].to_vec(), entry[13]));
+            }
+            var_type => {
+                headers.push((offset, current_folder.clone(), name, var_type, entry[13]));
+                padding.vars.push(VarPadding {
+                    name: entry[4..12].to_vec(),
+                    entry_tail: [entry[14], entry[15]],
+                    data_prefix: [0; 4],
+                });
+            }
+        }
+    }
+
+    // the table doesn't store lengths, each variable just runs up to the next one
+    let mut vars = Vec::new();
+    for (i, (offset, folder, name, var_type, attr)) in headers.iter().enumerate() {
+        let end = headers
+            .get(i + 1)
+            .map(|header| header.0)
+            .unwrap_or(file_size);
+        if *offset < entries_end + 6 || end < offset + TI89_VAR_OVERHEAD || end > file_size {
+            return Err(FormatError::Truncated);
+        }
+
+        let data = &bytes[offset + 4..end - 2];
+        verify_checksum(data, &bytes[end - 2..end])?;
+        padding.vars[i].data_prefix = bytes[*offset..offset + 4].try_into().unwrap();
+
+        vars.push(TiVar {
+            folder: folder.clone(),
+            name: name.clone(),
+            var_type: *var_type,
+            attr: *attr,
+            version: 0,
+            data: data.to_vec(),
+        });
+    }
+
+    Ok(VarFile {
+        machine_id,
+        folder,
+        comment: padded_string(&bytes[0x12..0x3A]),
+        vars,
+        short_headers: false,
+        padding,
+    })
+}
+
+fn parse_ti83p(bytes: &[u8]) -> Result<VarFile, FormatError> {
+    if bytes.len() < TI83P_DATA_OFFSET {
+        return Err(FormatError::Truncated);
+    }
+
+    let section_len = u16::from_le_bytes([bytes[0x35], bytes[0x36]]) as usize;
+    if bytes.len() < TI83P_DATA_OFFSET + section_len + 2 {
+        return Err(FormatError::Truncated);
+    }
+    let section = &bytes[TI83P_DATA_OFFSET..TI83P_DATA_OFFSET + section_len];
+    verify_checksum(
+        section,
+        &bytes[TI83P_DATA_OFFSET + section_len..TI83P_DATA_OFFSET + section_len + 2],
+    )?;
+
+    // the data section is just the variables back to back, each one
+    // [header length, data length, type, name, (version, flag,) data length again, data]
+    let mut vars = Vec::new();
+    let mut padding = FilePadding {
+        after_signature: bytes[0x08..0x0B].to_vec(),
+        comment: bytes[0x0B..0x35].to_vec(),
+        ..FilePadding::default()
+    };
+    let mut short_headers = false;
+    let mut rest = section;
+    while !rest.is_empty() {
+        if rest.len() < 4 {
+            return Err(FormatError::Truncated);
+        }
+        let header_len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        if header_len < TI83P_SHORT_HEADER_LEN as usize || rest.len() < 2 + header_len + 2 {
+            return Err(FormatError::Truncated);
+        }
+        let data_len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let data_start = 2 + header_len + 2;
+        if rest.len() < data_start + data_len {
+            return Err(FormatError::Truncated);
+        }
+
+        let (version, attr) = match header_len {
+            len if len >= TI83P_HEADER_LEN as usize => (rest[13], rest[14]),
+            _ => {
+                short_headers = true;
+                (0, 0)
+            }
+        };
+
+        vars.push(TiVar {
+            folder: String::new(),
+            name: padded_string(&rest[5..13]),
+            var_type: rest[4],
+            attr,
+            version,
+            data: rest[data_start..data_start + data_len].to_vec(),
+        });
+        padding.vars.push(VarPadding {
+            name: rest[5..13].to_vec(),
+            ..VarPadding::default()
+        });
+
+        rest = &rest[data_start + data_len..];
+    }
+
+    Ok(VarFile {
+        machine_id: MACHINE_ID_TI83P,
+        folder: String::new(),
+        comment: padded_string(&bytes[0x0B..0x35]),
+        vars,
+        short_headers,
+        padding,
+    })
+}
+
+fn write_ti89(file: &VarFile) -> Vec<u8> {
+    // a lone variable in the header's folder doesn't need a folder entry, unless the file it was read from had one.
+    // Anything else gets one per run of variables in the same folder, same as TiLP writes them
+    let needs_folders = file.vars.len() > 1
+        || file.vars.iter().any(|var| var.folder != file.folder)
+        || !file.padding.folder_entries.is_empty();
+
+    let mut folder_runs: Vec<(&str, usize)> = Vec::new();
+    if needs_folders {
+        for var in &file.vars {
+            match folder_runs.last_mut() {
+                Some((folder, count)) if *folder == var.folder => *count += 1,
+                _ => folder_runs.push((&var.folder, 1)),
+            }
+        }
+    }
+
+    let entry_count = file.vars.len() + folder_runs.len();
+    let mut offset = TI89_HEADER_LEN + entry_count * TI89_ENTRY_LEN + 6;
+
+    let default_padding = VarPadding::default();
+    let var_padding = |i: usize| file.padding.vars.get(i).unwrap_or(&default_padding);
+
+    let mut entries = Vec::new();
+    let mut vars = file.vars.iter().enumerate();
+    if needs_folders {
+        // folder entries point at the first variable in them
+        for (i, (folder, count)) in folder_runs.iter().enumerate() {
+            let (name, attr) = match file.padding.folder_entries.get(i) {
+                Some((name, attr)) => (padded_bytes_like(folder, 8, name), *attr),
+                None => (padded_bytes(folder, 8), 0x00),
+            };
+            entries.extend_from_slice(&(offset as u32).to_le_bytes());
+            entries.extend_from_slice(&name);
+            entries.extend_from_slice(&[TI89_FOLDER_TYPE, attr]);
+            entries.extend_from_slice(&(*count as u16).to_le_bytes());
+            for (i, var) in vars.by_ref().take(*count) {
+                ti89_var_entry(&mut entries, &mut offset, var, var_padding(i));
+            }
+        }
+    } else {
+        for (i, var) in vars {
+            ti89_var_entry(&mut entries, &mut offset, var, var_padding(i));
+        }
+    }
+    let file_size = offset;
+
+    let mut bytes = match file.machine_id {
+        MACHINE_ID_TI92P => TI92P_SIGNATURE.to_vec(),
+        _ => TI89_SIGNATURE.to_vec(),
+    };
+    match file.padding.after_signature.as_slice() {
+        after_signature @ [_, _] => bytes.extend_from_slice(after_signature),
+        _ => bytes.extend_from_slice(&[0x01, 0x00]),
+    }
+    bytes.extend_from_slice(&padded_bytes_like(&file.folder, 8, &file.padding.folder));
+    bytes.extend_from_slice(&padded_bytes_like(&file.comment, 40, &file.padding.comment));
+    bytes.extend_from_slice(&(entry_count as u16).to_le_bytes());
+    bytes.extend_from_slice(&entries);
+    bytes.extend_from_slice(&(file_size as u32).to_le_bytes());
+    bytes.extend_from_slice(&[0xA5, 0x5A]);
+    for (i, var) in file.vars.iter().enumerate() {
+        bytes.extend_from_slice(&var_padding(i).data_prefix);
+        bytes.extend_from_slice(&var.data);
+        bytes.extend_from_slice(&checksum(&var.data).to_le_bytes());
+    }
+
+    bytes
+}
+
+// appends `var`'s table entry, moving `offset` on to where the next variable goes
+fn ti89_var_entry(entries: &mut Vec<u8>, offset: &mut usize, var: &TiVar, padding: &VarPadding) {
+    entries.extend_from_slice(&(*offset as u32).to_le_bytes());
+    entries.extend_from_slice(&padded_bytes_like(&var.name, 8, &padding.name));
+    entries.extend_from_slice(&[var.var_type, var.attr]);
+    entries.extend_from_slice(&padding.entry_tail);
+    *offset += var.data.len() + TI89_VAR_OVERHEAD;
+}
+
+fn write_ti83p(file: &VarFile) -> Vec<u8> {
+    let mut section = Vec::new();
+    for (i, var) in file.vars.iter().enumerate() {
+        let data_len = (var.data.len() as u16).to_le_bytes();
+
+        if file.short_headers {
+            section.extend_from_slice(&TI83P_SHORT_HEADER_LEN.to_le_bytes());
+        } else {
+            section.extend_from_slice(&TI83P_HEADER_LEN.to_le_bytes());
+        }
+        section.extend_from_slice(&data_len);
+        section.push(var.var_type);
+        let name_padding = file
+            .padding
+            .vars
+            .get(i)
+            .map(|padding| padding.name.as_slice());
+        section.extend_from_slice(&padded_bytes_like(
+            &var.name,
+            8,
+            name_padding.unwrap_or_default(),
+        ));
+        if !file.short_headers {
+            section.extend_from_slice(&[var.version, var.attr]);
+        }
+        section.extend_from_slice(&data_len);
+        section.extend_from_slice(&var.data);
+    }
+
+    let mut bytes = TI83P_SIGNATURE.to_vec();
+    match file.padding.after_signature.as_slice() {
+        after_signature @ [_, _, _] => bytes.extend_from_slice(after_signature),
+        _ => bytes.extend_from_slice(&[0x1A, 0x0A, 0x00]),
+    }
+    bytes.extend_from_slice(&padded_bytes_like(&file.comment, 42, &file.padding.comment));
+    bytes.extend_from_slice(&(section.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&section);
+    bytes.extend_from_slice(&checksum(&section).to_le_bytes());
+
+    bytes
+}
+
+// the reverse of `padded_string`, one byte per char so that anything we read comes back out the same
+fn padded_bytes(string: &str, len: usize) -> Vec<u8> {
+    let mut bytes = string.chars().map(|c| c as u8).collect::<Vec<u8>>();
+    bytes.resize(len, 0);
+    bytes
+}
+
+// the same, but padded the way `original` was if that's where `string` came from
+fn padded_bytes_like(string: &str, len: usize, original: &[u8]) -> Vec<u8> {
+    if original.len() == len && padded_string(original) == string {
+        original.to_vec()
+    } else {
+        padded_bytes(string, len)
+    }
+}
+
+// names, folders and comments are all zero-padded out to a fixed width
+pub(crate) fn padded_string(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|byte| **byte != 0)
+        .map(|byte| *byte as char)
+        .collect()
+}
+
+fn verify_checksum(data: &[u8], checksum_bytes: &[u8]) -> Result<(), FormatError> {
+    let stored = u16::from_le_bytes([checksum_bytes[0], checksum_bytes[1]]);
+    let calculated = checksum(data);
+
+    if stored != calculated {
+        return Err(FormatError::BadChecksum(stored, calculated));
+    }
+
+    Ok(())
+}
@@ -1,14 +1,19 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
 use debug_print::debug_eprintln;
-use strum::{EnumIter, IntoEnumIterator};
+use strum::{EnumIter, EnumString, IntoEnumIterator};
 use uinput::{
     event::{
         keyboard::{Key, KeyPad},
-        Keyboard,
+        Code, Keyboard,
     },
     Device, Event,
 };
 
-#[derive(Eq, Hash, PartialEq, Debug, EnumIter)]
+#[derive(Eq, Hash, PartialEq, Debug, Copy, Clone, EnumIter, EnumString)]
 pub enum CalcKey {
     CursorDown,
     CursorRight,
@@ -113,11 +118,28 @@ pub enum VirtualKeyboardCreationError {
     CreationFailed(uinput::Error),
 }
 
+// wherever decoded key presses end up. Normally that's the uinput `VirtualKeyboard`
+pub trait KeySink {
+    fn press_key(&mut self, key: &CalcKey) -> uinput::Result<()>;
+
+    fn release_key(&mut self, key: &CalcKey) -> uinput::Result<()>;
+
+    fn sync(&mut self) -> uinput::Result<()>;
+
+    // let go of everything currently held down, e.g. because the link died mid-press
+    fn release_all_keys(&mut self) -> uinput::Result<()>;
+}
+
 pub struct VirtualKeyboard {
     handle: Device,
+    held_keys: HashSet<CalcKey>,
 }
 impl VirtualKeyboard {
     pub fn new() -> Result<VirtualKeyboard, VirtualKeyboardCreationError> {
+        VirtualKeyboard::with_name("i68apollo")
+    }
+
+    pub fn with_name(name: &str) -> Result<VirtualKeyboard, VirtualKeyboardCreationError> {
         let mut virtual_kbd = match uinput::default() {
             Ok(vkbd) => vkbd,
             Err(e) => {
@@ -127,7 +149,7 @@ impl VirtualKeyboard {
                 });
             }
         };
-        virtual_kbd = match virtual_kbd.name("i68apollo") {
+        virtual_kbd = match virtual_kbd.name(name) {
             Ok(vkbd) => vkbd,
             Err(e) => {
                 return Err(VirtualKeyboardCreationError::SetNameFailed(e));
@@ -152,30 +174,141 @@ impl VirtualKeyboard {
             }
         };
 
-        Ok(VirtualKeyboard { handle })
+        Ok(VirtualKeyboard {
+            handle,
+            held_keys: HashSet::new(),
+        })
     }
+}
 
-    pub fn press_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+impl KeySink for VirtualKeyboard {
+    fn press_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
         let key_event = map_key_to_key(key);
 
         debug_eprintln!("vkbd: pressing {key_event:?}");
 
-        self.handle.press(&key_event)
+        self.handle.press(&key_event)?;
+        self.held_keys.insert(*key);
+        Ok(())
     }
 
-    pub fn release_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+    fn release_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
         let key_event = map_key_to_key(key);
 
         debug_eprintln!("vkbd: releasing {key_event:?}");
 
-        self.handle.release(&key_event)
+        self.handle.release(&key_event)?;
+        self.held_keys.remove(key);
+        Ok(())
     }
 
-    pub fn sync(&mut self) -> uinput::Result<()> {
+    fn sync(&mut self) -> uinput::Result<()> {
         debug_eprintln!("vkbd: syncing");
 
         self.handle.synchronize()
     }
+
+    fn release_all_keys(&mut self) -> uinput::Result<()> {
+        for key in self.held_keys.drain().collect::<Vec<CalcKey>>() {
+            let key_event = map_key_to_key(&key);
+
+            debug_eprintln!("vkbd: releasing held {key_event:?}");
+
+            self.handle.release(&key_event)?;
+        }
+
+        self.sync()
+    }
+}
+
+// the one keyboard several calculators type into. It counts how many of them are holding each key, so a key only goes
+// up once the last of them lets go
+pub struct MergedKeyboard {
+    virtual_kbd: Box<dyn KeySink + Send>,
+    hold_counts: HashMap<CalcKey, usize>,
+}
+impl MergedKeyboard {
+    pub fn new(virtual_kbd: Box<dyn KeySink + Send>) -> MergedKeyboard {
+        MergedKeyboard {
+            virtual_kbd,
+            hold_counts: HashMap::new(),
+        }
+    }
+
+    fn press_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        let hold_count = self.hold_counts.entry(*key).or_insert(0);
+        if *hold_count == 0 {
+            self.virtual_kbd.press_key(key)?;
+        }
+        *hold_count += 1;
+        Ok(())
+    }
+
+    fn release_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        match self.hold_counts.get_mut(key) {
+            Some(hold_count) if *hold_count > 1 => {
+                *hold_count -= 1;
+                Ok(())
+            }
+            _ => {
+                self.hold_counts.remove(key);
+                self.virtual_kbd.release_key(key)
+            }
+        }
+    }
+}
+
+// one of several calculators typing into the same `MergedKeyboard`. Each one keeps track of what it's holding down
+// itself, so losing one calculator doesn't let go of keys the others are still holding
+pub struct SharedKeyboard {
+    merged_kbd: Arc<Mutex<MergedKeyboard>>,
+    held_keys: HashSet<CalcKey>,
+}
+impl SharedKeyboard {
+    pub fn new(merged_kbd: Arc<Mutex<MergedKeyboard>>) -> SharedKeyboard {
+        SharedKeyboard {
+            merged_kbd,
+            held_keys: HashSet::new(),
+        }
+    }
+}
+
+impl KeySink for SharedKeyboard {
+    // a key this calculator's already holding doesn't count twice
+    fn press_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        if self.held_keys.insert(*key) {
+            self.merged_kbd.lock().unwrap().press_key(key)?;
+        }
+        Ok(())
+    }
+
+    fn release_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        if self.held_keys.remove(key) {
+            self.merged_kbd.lock().unwrap().release_key(key)?;
+        }
+        Ok(())
+    }
+
+    fn sync(&mut self) -> uinput::Result<()> {
+        self.merged_kbd.lock().unwrap().virtual_kbd.sync()
+    }
+
+    fn release_all_keys(&mut self) -> uinput::Result<()> {
+        let mut merged_kbd = self.merged_kbd.lock().unwrap();
+        for key in self.held_keys.drain() {
+            merged_kbd.release_key(&key)?;
+        }
+
+        merged_kbd.virtual_kbd.sync()
+    }
+}
+
+// the other way round from the virtual keyboard: every calculator key that a Linux keycode (as in input-event-codes.h)
+// stands in for, in `CalcKey` order. Some share a key, so which one's meant depends on the model
+pub fn calc_keys_for_linux_key(code: u16) -> Vec<CalcKey> {
+    CalcKey::iter()
+        .filter(|key| map_key_to_key(key).code() == code as i32)
+        .collect()
 }
 
 fn map_key_to_key(key: &CalcKey) -> Keyboard {
@@ -1,21 +1,114 @@
-use cable::Cable;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
 use calc::CalcHandle;
 use debug_print::debug_eprintln;
-use keyboard::{CalcKey, VirtualKeyboard};
+use keyboard::{CalcKey, KeySink};
+use link::{LatencyStats, LinkIoError, LinkTransport};
+use reader::{read_link, KeyEvent, LinkEvent, OverflowPolicy};
 
+pub mod args;
+pub mod backup;
 pub mod cable;
 pub mod calc;
+pub mod formats;
 pub mod keyboard;
+pub mod link;
+pub mod reader;
+pub mod remote;
+pub mod screen;
+pub mod shutdown;
+pub mod soyuz;
+pub mod tokens;
+pub mod vars;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct RunConfig {
+    // how many decoded frames may queue up between the link reader and the virtual keyboard
+    pub channel_capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+    // whatever was agreed on in the handshake, see `SessionConfig`
+    pub quit_key: CalcKey,
+    // how long soyuz can go without sending anything before it's given up on. Only for when it's agreed to send
+    // heartbeats, otherwise a calculator nobody's touching would time out
+    pub link_timeout: Option<Duration>,
+}
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            channel_capacity: 64,
+            overflow_policy: OverflowPolicy::Block,
+            quit_key: CalcKey::ON,
+            link_timeout: None,
+        }
+    }
+}
+
+pub fn run(
+    link: &mut dyn LinkTransport,
+    calc: &mut dyn CalcHandle,
+    virtual_kbd: &mut dyn KeySink,
+    config: &RunConfig,
+) -> Result<(), LinkIoError> {
+    let stop = AtomicBool::new(false);
+    let (sender, events) = mpsc::sync_channel(config.channel_capacity);
+    let mut latency = LatencyStats::default();
+
+    let reader_link = &mut *link;
+    let (result, frames_coalesced) = thread::scope(|scope| {
+        let stop = &stop;
+        let reader = scope.spawn(move || read_link(reader_link, calc, sender, stop, config));
+
+        let result = forward_keys(&events, virtual_kbd, config.quit_key, &mut latency);
+
+        // the reader notices within one poll interval, or straight away if it's stuck waiting on a full channel
+        stop.store(true, Ordering::Relaxed);
+        drop(events);
 
-pub fn run(cable: &mut Cable, mut calc: Box<dyn CalcHandle>, virtual_kbd: &mut VirtualKeyboard) {
-    'outer: loop {
-        for keystate in calc.get_keys(cable) {
-            let (key, pressed) = keystate;
+        let frames_coalesced = reader.join().expect("link reader panicked!");
+        debug_eprintln!("{frames_coalesced} frames coalesced");
+
+        (result, frames_coalesced)
+    });
+
+    let stats = link.stats_mut();
+    stats.frames_coalesced += frames_coalesced;
+    stats.latency.merge(&latency);
+
+    result
+}
+
+fn forward_keys(
+    events: &Receiver<LinkEvent>,
+    virtual_kbd: &mut dyn KeySink,
+    quit_key: CalcKey,
+    latency: &mut LatencyStats,
+) -> Result<(), LinkIoError> {
+    for event in events.iter() {
+        let frame = match event {
+            LinkEvent::Frame(frame) => frame,
+            LinkEvent::Error(e) => {
+                // whatever was down when the link went away would otherwise stay down forever
+                virtual_kbd
+                    .release_all_keys()
+                    .expect("can't release held keys!");
+                return Err(e);
+            }
+        };
+
+        for key_event in &frame {
+            let KeyEvent { key, pressed, .. } = *key_event;
 
             debug_eprintln!("{key:?}, pressed?: {pressed}");
 
-            if key == CalcKey::ON && pressed {
-                break 'outer;
+            if key == quit_key && pressed {
+                return Ok(());
             }
 
             if pressed {
@@ -26,5 +119,16 @@ pub fn run(cable: &mut Cable, mut calc: Box<dyn CalcHandle>, virtual_kbd: &mut V
         }
 
         virtual_kbd.sync().expect("can't sync!");
+
+        let synced = Instant::now();
+        for key_event in &frame {
+            latency.record(synced.duration_since(key_event.timestamp));
+        }
     }
+
+    // the reader only hangs up without an error when apollo's shutting down, which mustn't leave anything held either
+    virtual_kbd
+        .release_all_keys()
+        .expect("can't release held keys!");
+    Ok(())
 }
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use crate::cable::CableIoError;
+
+pub mod mock;
+pub mod packet;
+pub mod socket;
+
+// anything that can carry bytes between apollo and soyuz. The SilverLink `Cable` is the only "real" one, but the calc
+// and handshake code doesn't care what's underneath
+pub trait LinkTransport: Send {
+    fn read_bytes(
+        &mut self,
+        bytes_expected: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, LinkIoError>;
+
+    fn write_bytes(&mut self, bytes: &[u8], timeout: Duration) -> Result<(), LinkIoError>;
+
+    fn stats(&self) -> &LinkStats;
+
+    fn stats_mut(&mut self) -> &mut LinkStats;
+}
+
+// what a link has been up to since it was opened. The transport keeps the byte counts itself, the calc and `run` fill in
+// the rest as frames get decoded and forwarded
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct LinkStats {
+    pub bytes_read_overall: usize,
+    pub frames_read: u64,
+    // frames with bits set where the model has no key, the wrong length, or a bad checksum. Either soyuz sent garbage or
+    // we've slipped out of step with it
+    pub malformed_reads: u64,
+    // reads that came back with more bytes than the frame being read needed. Harmless, the rest gets buffered, but a
+    // lot of them means frames are piling up on the link's end
+    pub overreads: u64,
+    pub frames_coalesced: u64,
+    pub latency: LatencyStats,
+}
+impl LinkStats {
+    pub fn merge(&mut self, other: &LinkStats) {
+        self.bytes_read_overall += other.bytes_read_overall;
+        self.frames_read += other.frames_read;
+        self.malformed_reads += other.malformed_reads;
+        self.overreads += other.overreads;
+        self.frames_coalesced += other.frames_coalesced;
+        self.latency.merge(&other.latency);
+    }
+}
+
+// time from a frame coming off the link to its keys being synced to the virtual keyboard
+#[derive(Eq, PartialEq, Debug, Copy, Clone, Default)]
+pub struct LatencyStats {
+    pub samples: u64,
+    pub total: Duration,
+    pub min: Option<Duration>,
+    pub max: Duration,
+}
+impl LatencyStats {
+    pub fn record(&mut self, latency: Duration) {
+        self.samples += 1;
+        self.total += latency;
+        self.min = Some(self.min.map_or(latency, |min| min.min(latency)));
+        self.max = self.max.max(latency);
+    }
+
+    pub fn merge(&mut self, other: &LatencyStats) {
+        self.samples += other.samples;
+        self.total += other.total;
+        self.min = match (self.min, other.min) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        match self.samples {
+            0 => None,
+            samples => Some(self.total / samples as u32),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum LinkIoError {
+    Timeout,
+    Disconnected,
+    Stall,
+    Overflow,
+    // soyuz agreed to send heartbeats, then went quiet for longer than `RunConfig::link_timeout`. The calculator's
+    // probably off, or soyuz crashed
+    HeartbeatLost,
+    Other,
+}
+impl LinkIoError {
+    // whether it's worth trying the same transfer again, as opposed to giving up on the link entirely
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, LinkIoError::Timeout | LinkIoError::Stall)
+    }
+}
+impl From<CableIoError> for LinkIoError {
+    fn from(e: CableIoError) -> Self {
+        match e {
+            CableIoError::Timeout => LinkIoError::Timeout,
+            CableIoError::Disconnected => LinkIoError::Disconnected,
+            CableIoError::Stall => LinkIoError::Stall,
+            CableIoError::Overflow => LinkIoError::Overflow,
+            CableIoError::Other(_) => LinkIoError::Other,
+        }
+    }
+}
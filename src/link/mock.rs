@@ -0,0 +1,97 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::calc::{ReportFormat, SessionConfig};
+
+use super::{LinkIoError, LinkStats, LinkTransport};
+
+// plays back a scripted byte stream in place of soyuz and remembers everything apollo sends back. Once the script runs
+// dry the link reports itself as disconnected, which is also how the tests get `run` to return
+#[derive(Eq, PartialEq, Debug, Clone, Default)]
+pub struct MockLink {
+    script: VecDeque<u8>,
+    written: Vec<u8>,
+    // time out instead once the script runs dry, like a soyuz that's stopped sending
+    quiet: bool,
+
+    stats: LinkStats,
+}
+impl MockLink {
+    pub fn new() -> MockLink {
+        MockLink {
+            script: VecDeque::new(),
+            written: Vec::new(),
+            quiet: false,
+            stats: LinkStats::default(),
+        }
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> &mut MockLink {
+        self.script.extend(bytes);
+        self
+    }
+
+    // everything soyuz sends before the key matrix starts flowing: ready byte, version triple, machine ID, then its
+    // answer to the configuration, which is to go along with the default one but send full matrices, unframed
+    pub fn push_handshake(&mut self, soyuz_ver: (u8, u8, u8), machine_id: u8) -> &mut MockLink {
+        self.push_bytes(&[0x50, soyuz_ver.0, soyuz_ver.1, soyuz_ver.2, machine_id]);
+        let config = SessionConfig {
+            report_format: ReportFormat::FullMatrix,
+            flags: 0,
+            ..SessionConfig::default()
+        };
+        if let Some(config) = config.encode(machine_id) {
+            self.push_bytes(&config);
+        }
+        self
+    }
+
+    pub fn push_frame(&mut self, key_matrix: &[u8]) -> &mut MockLink {
+        self.push_bytes(key_matrix)
+    }
+
+    pub fn go_quiet(&mut self) -> &mut MockLink {
+        self.quiet = true;
+        self
+    }
+
+    pub fn bytes_remaining(&self) -> usize {
+        self.script.len()
+    }
+
+    pub fn written(&self) -> &[u8] {
+        &self.written
+    }
+}
+
+impl LinkTransport for MockLink {
+    fn read_bytes(
+        &mut self,
+        bytes_expected: usize,
+        _timeout: Duration,
+    ) -> Result<Vec<u8>, LinkIoError> {
+        if self.script.len() < bytes_expected {
+            return Err(if self.quiet {
+                LinkIoError::Timeout
+            } else {
+                LinkIoError::Disconnected
+            });
+        }
+
+        self.stats.bytes_read_overall += bytes_expected;
+
+        Ok(self.script.drain(0..bytes_expected).collect::<Vec<u8>>())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8], _timeout: Duration) -> Result<(), LinkIoError> {
+        self.written.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut LinkStats {
+        &mut self.stats
+    }
+}
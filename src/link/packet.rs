@@ -0,0 +1,268 @@
+use std::time::Duration;
+
+use debug_print::debug_eprintln;
+
+use crate::calc::{MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P};
+
+use super::{LinkIoError, LinkTransport};
+
+// TI's own link protocol, the one the calculator's OS speaks when soyuz isn't running. Every packet is
+// [machine ID, command, length (LE u16)], then for the commands that carry one, the data and a checksum (LE u16)
+
+// first byte of a packet, i.e. who's talking to whom
+pub const PC_TO_TI89: u8 = 0x08; // the 92+ takes these too
+pub const PC_TO_TI83P: u8 = 0x23;
+pub const TI89_TO_PC: u8 = 0x98;
+pub const TI92P_TO_PC: u8 = 0x88;
+pub const TI83P_TO_PC: u8 = 0x73;
+
+pub const HEADER_LEN: usize = 4;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum Command {
+    // variable header, in reply to a request
+    Var,
+    // what OS and boot code the calc's running
+    Ver,
+    // clear to send
+    Cts,
+    Data,
+    // skip/exit, i.e. the calc won't take the variable. The data's why, starting with a rejection code
+    Ske,
+    Ack,
+    // checksum error, send that again
+    Err,
+    // are you ready?
+    Rdy,
+    // screenshot request
+    Scr,
+    // remote control keypress. The keycode goes where the length would be
+    Cmd,
+    // delete a variable
+    Del,
+    // end of transmission
+    Eot,
+    // request for a variable, or the directory
+    Req,
+    // request to send
+    Rts,
+}
+impl Command {
+    pub fn id(self) -> u8 {
+        match self {
+            Command::Var => 0x06,
+            Command::Ver => 0x2D,
+            Command::Cts => 0x09,
+            Command::Data => 0x15,
+            Command::Ske => 0x36,
+            Command::Ack => 0x56,
+            Command::Err => 0x5A,
+            Command::Rdy => 0x68,
+            Command::Scr => 0x6D,
+            Command::Cmd => 0x87,
+            Command::Del => 0x88,
+            Command::Eot => 0x92,
+            Command::Req => 0xA2,
+            Command::Rts => 0xC9,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Command> {
+        match id {
+            0x06 => Some(Command::Var),
+            0x2D => Some(Command::Ver),
+            0x09 => Some(Command::Cts),
+            0x15 => Some(Command::Data),
+            0x36 => Some(Command::Ske),
+            0x56 => Some(Command::Ack),
+            0x5A => Some(Command::Err),
+            0x68 => Some(Command::Rdy),
+            0x6D => Some(Command::Scr),
+            0x87 => Some(Command::Cmd),
+            0x88 => Some(Command::Del),
+            0x92 => Some(Command::Eot),
+            0xA2 => Some(Command::Req),
+            0xC9 => Some(Command::Rts),
+            _ => None,
+        }
+    }
+
+    // whether a data section and checksum follow the header. For everything else the length field is really just a
+    // parameter, usually zero
+    pub fn has_data(self) -> bool {
+        matches!(
+            self,
+            Command::Var
+                | Command::Data
+                | Command::Ske
+                | Command::Del
+                | Command::Req
+                | Command::Rts
+        )
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum PacketError {
+    UnknownCommand(u8),
+    // (received, calculated)
+    BadChecksum(u16, u16),
+    // ran out of bytes partway through a packet
+    Truncated,
+    LinkError(LinkIoError),
+}
+impl From<LinkIoError> for PacketError {
+    fn from(e: LinkIoError) -> Self {
+        PacketError::LinkError(e)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Packet {
+    pub machine_id: u8,
+    pub command: Command,
+    // the two bytes after the command. The data's length if there is any, otherwise a parameter of its own
+    pub param: u16,
+    pub data: Vec<u8>,
+}
+impl Packet {
+    pub fn new(machine_id: u8, command: Command) -> Packet {
+        Packet::with_param(machine_id, command, 0)
+    }
+
+    pub fn with_param(machine_id: u8, command: Command, param: u16) -> Packet {
+        Packet {
+            machine_id,
+            command,
+            param,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn with_data(machine_id: u8, command: Command, data: Vec<u8>) -> Packet {
+        Packet {
+            machine_id,
+            command,
+            param: data.len() as u16,
+            data,
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.machine_id, self.command.id()];
+
+        if self.command.has_data() {
+            bytes.extend_from_slice(&(self.data.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&self.data);
+            bytes.extend_from_slice(&checksum(&self.data).to_le_bytes());
+        } else {
+            bytes.extend_from_slice(&self.param.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    // decodes one packet from the front of `bytes`, returning it along with how many bytes it took up
+    pub fn decode(bytes: &[u8]) -> Result<(Packet, usize), PacketError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(PacketError::Truncated);
+        }
+
+        let (mut packet, data_len) = decode_header(bytes[0..HEADER_LEN].try_into().unwrap())?;
+        if !packet.command.has_data() {
+            return Ok((packet, HEADER_LEN));
+        }
+
+        let packet_len = HEADER_LEN + data_len + 2;
+        if bytes.len() < packet_len {
+            return Err(PacketError::Truncated);
+        }
+
+        packet.data = bytes[HEADER_LEN..HEADER_LEN + data_len].to_vec();
+        verify_checksum(&packet.data, &bytes[HEADER_LEN + data_len..packet_len])?;
+
+        Ok((packet, packet_len))
+    }
+}
+
+// 16-bit sum of the data bytes, overflow and all
+pub fn checksum(data: &[u8]) -> u16 {
+    let mut checksum: u16 = 0;
+    for byte in data {
+        checksum = checksum.wrapping_add(*byte as u16);
+    }
+    checksum
+}
+
+// what the PC has to put at the front of its packets to talk to a given model
+pub fn pc_machine_id(machine_id: u8) -> Option<u8> {
+    match machine_id {
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => Some(PC_TO_TI89),
+        MACHINE_ID_TI83P => Some(PC_TO_TI83P),
+        _ => None,
+    }
+}
+
+pub fn write_packet(
+    link: &mut dyn LinkTransport,
+    packet: &Packet,
+    timeout: Duration,
+) -> Result<(), LinkIoError> {
+    debug_eprintln!(
+        "packet: sending {:?} to {:#04x}, {} bytes of data",
+        packet.command,
+        packet.machine_id,
+        packet.data.len()
+    );
+    link.write_bytes(&packet.encode(), timeout)
+}
+
+pub fn read_packet(link: &mut dyn LinkTransport, timeout: Duration) -> Result<Packet, PacketError> {
+    let header = link.read_bytes(HEADER_LEN, timeout)?;
+    let (mut packet, data_len) = decode_header(header[0..HEADER_LEN].try_into().unwrap())?;
+
+    if packet.command.has_data() {
+        let rest = link.read_bytes(data_len + 2, timeout)?;
+        packet.data = rest[0..data_len].to_vec();
+        verify_checksum(&packet.data, &rest[data_len..])?;
+    }
+
+    debug_eprintln!(
+        "packet: got {:?} from {:#04x}, {} bytes of data",
+        packet.command,
+        packet.machine_id,
+        packet.data.len()
+    );
+    Ok(packet)
+}
+
+// the packet, minus any data, and how long that data is
+fn decode_header(header: [u8; HEADER_LEN]) -> Result<(Packet, usize), PacketError> {
+    let command = match Command::from_id(header[1]) {
+        Some(command) => command,
+        None => {
+            return Err(PacketError::UnknownCommand(header[1]));
+        }
+    };
+    let param = u16::from_le_bytes([header[2], header[3]]);
+
+    let data_len = if command.has_data() {
+        param as usize
+    } else {
+        0
+    };
+
+    Ok((Packet::with_param(header[0], command, param), data_len))
+}
+
+fn verify_checksum(data: &[u8], checksum_bytes: &[u8]) -> Result<(), PacketError> {
+    let received = u16::from_le_bytes([checksum_bytes[0], checksum_bytes[1]]);
+    let calculated = checksum(data);
+
+    if received != calculated {
+        debug_eprintln!("packet: bad checksum, got {received:#06x}, expected {calculated:#06x}");
+        return Err(PacketError::BadChecksum(received, calculated));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    time::Duration,
+};
+
+use super::{LinkIoError, LinkStats, LinkTransport};
+
+// talks to soyuz over a Unix socket instead of a cable. In practice the thing on the other end is `i68soyuz-sim`
+pub struct SocketLink {
+    stream: UnixStream,
+    // same deal as the `Cable`: a read that times out partway through shouldn't lose the bytes it did get
+    byte_buffer: Vec<u8>,
+
+    stats: LinkStats,
+}
+impl SocketLink {
+    pub fn connect<P: AsRef<Path>>(path: P) -> io::Result<SocketLink> {
+        let stream = UnixStream::connect(path)?;
+
+        Ok(SocketLink::from_stream(stream))
+    }
+
+    pub fn from_stream(stream: UnixStream) -> SocketLink {
+        SocketLink {
+            stream,
+            byte_buffer: Vec::new(),
+            stats: LinkStats::default(),
+        }
+    }
+}
+
+impl LinkTransport for SocketLink {
+    fn read_bytes(
+        &mut self,
+        bytes_expected: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, LinkIoError> {
+        // libusb treats a zero timeout as "wait forever", so we do too
+        let timeout = if timeout.is_zero() {
+            None
+        } else {
+            Some(timeout)
+        };
+        self.stream
+            .set_read_timeout(timeout)
+            .map_err(map_io_error)?;
+
+        while self.byte_buffer.len() < bytes_expected {
+            let mut buf: [u8; 512] = [0; 512];
+            let read_size = self.stream.read(&mut buf).map_err(map_io_error)?;
+            if read_size == 0 {
+                return Err(LinkIoError::Disconnected);
+            }
+
+            self.stats.bytes_read_overall += read_size;
+            if read_size > bytes_expected - self.byte_buffer.len() {
+                self.stats.overreads += 1;
+            }
+
+            self.byte_buffer.extend_from_slice(&buf[0..read_size]);
+        }
+
+        Ok(self
+            .byte_buffer
+            .drain(0..bytes_expected)
+            .collect::<Vec<u8>>())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8], timeout: Duration) -> Result<(), LinkIoError> {
+        let timeout = if timeout.is_zero() {
+            None
+        } else {
+            Some(timeout)
+        };
+        self.stream
+            .set_write_timeout(timeout)
+            .map_err(map_io_error)?;
+
+        self.stream.write_all(bytes).map_err(map_io_error)
+    }
+
+    fn stats(&self) -> &LinkStats {
+        &self.stats
+    }
+
+    fn stats_mut(&mut self) -> &mut LinkStats {
+        &mut self.stats
+    }
+}
+
+fn map_io_error(e: io::Error) -> LinkIoError {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => LinkIoError::Timeout,
+        io::ErrorKind::UnexpectedEof
+        | io::ErrorKind::BrokenPipe
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected => LinkIoError::Disconnected,
+        _ => LinkIoError::Other,
+    }
+}
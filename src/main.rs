@@ -1,15 +1,73 @@
-use std::time::Instant;
+use std::{
+    env, fs, io, mem,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use i68apollo::{
-    cable::{Cable, CableCreationError},
-    calc::{apollo_version, HandshakeError, I68MetaInfo},
-    keyboard::{VirtualKeyboard, VirtualKeyboardCreationError},
+    args::{
+        usage, ApolloArgs, ArgsError, BackupCommand, ClockCommand, LinkChoice, RemoteInput,
+        VarsCommand,
+    },
+    backup::{backup, restore, Backup},
+    cable::{
+        hotplug::{CableWatcher, CableWatcherCreationError},
+        Cable, CableCreationError, CableSelector,
+    },
+    calc::{
+        apollo_version, model_name, CalcHandle, HandshakeError, I68MetaInfo, SessionConfig,
+        FLAG_HEARTBEAT, MACHINE_ID_TI83P, MACHINE_ID_TI89,
+    },
+    formats::{is_archived, type_name, TiVar, VarFile, TI89_ATTR_LOCKED},
+    keyboard::{
+        KeySink, MergedKeyboard, SharedKeyboard, VirtualKeyboard, VirtualKeyboardCreationError,
+    },
+    link::{packet::PacketError, socket::SocketLink, LinkIoError, LinkStats, LinkTransport},
+    remote::{char_key, evdev::EvdevKeyboard, send_key, RemoteKeyboard},
     run,
+    screen::{screenshot, RenderStyle},
+    shutdown,
+    soyuz::{install, launch, load_bundle, BundleError, InstallOutcome, LaunchError},
+    tokens::{program_to_source, source_to_program, TokenError},
+    vars::{
+        delete_var, find_var, get_clock, get_var, list_vars, probe_model, send_var, set_clock,
+        CalcClock, TransferError, CLOCK_EPOCH,
+    },
+    RunConfig,
 };
 
-fn init_cable() -> Result<Cable, ()> {
+fn list_cables() -> Result<(), ()> {
+    let cables = match Cable::list() {
+        Ok(cables) => cables,
+        Err(e) => {
+            if let CableCreationError::GetDevicesListFailed(e) = e {
+                eprintln!("Couldn't get USB devices list. Reason: {e}");
+            }
+            return Err(());
+        }
+    };
+
+    if cables.is_empty() {
+        println!("No SilverLink cables found");
+    }
+    for info in cables {
+        println!(
+            "bus {:03} address {:03} port {:<10} serial {}",
+            info.bus_number,
+            info.address,
+            info.port_path(),
+            info.serial.as_deref().unwrap_or("(none)")
+        );
+    }
+
+    Ok(())
+}
+
+fn init_cable(selector: &CableSelector) -> Result<Cable, ()> {
     eprintln!("Initializing SilverLink cable...");
-    return match Cable::new() {
+    return match Cable::open(selector) {
         Ok(cable) => {
             eprintln!("SilverLink successfully initialized\n");
             Ok(cable)
@@ -23,6 +81,9 @@ fn init_cable() -> Result<Cable, ()> {
                 }
                 CableCreationError::NoCableFound => {
                     eprintln!("Couldn't find SilverLink cable. Is it plugged in?");
+                    if *selector != CableSelector::First {
+                        eprintln!("Run with --list-cables to see which cables are connected");
+                    }
                 }
                 CableCreationError::ClaimInterfaceFailed(e) => {
                     eprintln!("Couldn't claim cable interface 0x00. Reason: {e}");
@@ -42,9 +103,26 @@ fn init_cable() -> Result<Cable, ()> {
     };
 }
 
-fn init_vkbd() -> Result<VirtualKeyboard, ()> {
-    eprintln!("Creating virtual keyboard...");
-    return match VirtualKeyboard::new() {
+fn init_socket(path: &Path) -> Result<SocketLink, ()> {
+    eprintln!("Connecting to {}...", path.display());
+    match SocketLink::connect(path) {
+        Ok(socket) => {
+            eprintln!("Connected\n");
+            Ok(socket)
+        }
+
+        Err(e) => {
+            eprintln!("Connection failed\n");
+            eprintln!("Couldn't connect to socket. Reason: {e}");
+            eprintln!("Is i68soyuz-sim running?");
+            Err(())
+        }
+    }
+}
+
+fn init_vkbd(name: &str) -> Result<VirtualKeyboard, ()> {
+    eprintln!("Creating virtual keyboard \"{name}\"...");
+    return match VirtualKeyboard::with_name(name) {
         Ok(vkbd) => {
             eprintln!("Virtual keyboard created\n");
             Ok(vkbd)
@@ -75,11 +153,206 @@ fn init_vkbd() -> Result<VirtualKeyboard, ()> {
     };
 }
 
-fn init_calc(cable: &mut Cable) -> Result<I68MetaInfo, ()> {
+fn print_link_error(e: LinkIoError) {
+    match e {
+        LinkIoError::Timeout => {
+            eprintln!("Link timed out");
+        }
+        LinkIoError::Disconnected => {
+            eprintln!("Link disconnected. Was the cable unplugged?");
+        }
+        LinkIoError::Stall => {
+            eprintln!("Link endpoint stalled");
+        }
+        LinkIoError::Overflow => {
+            eprintln!("Link sent more data than expected");
+        }
+        LinkIoError::HeartbeatLost => {
+            eprintln!("i68soyuz went quiet. Did the calculator turn off, or i68soyuz crash? Any held keys were let go");
+        }
+        LinkIoError::Other => {
+            eprintln!("Unknown link error");
+        }
+    }
+}
+
+fn print_transfer_error(e: TransferError) {
+    match e {
+        TransferError::PacketError(PacketError::LinkError(e)) => {
+            print_link_error(e);
+        }
+        TransferError::PacketError(PacketError::BadChecksum(_, _)) => {
+            eprintln!("Calculator sent a corrupted packet");
+        }
+        TransferError::PacketError(_) => {
+            eprintln!("Calculator sent something that wasn't a valid packet");
+        }
+        TransferError::UnexpectedPacket(command) => {
+            eprintln!("Calculator answered out of turn, with {command:?}");
+        }
+        TransferError::Refused => {
+            eprintln!("Calculator refused the transfer. Is there enough free memory?");
+        }
+        TransferError::NotFound => {
+            eprintln!("No such variable on calculator");
+        }
+        TransferError::UnsupportedModel(machine_id) => {
+            eprintln!("Unknown/unsupported calculator: {machine_id:#04x}");
+        }
+    }
+}
+
+fn print_token_error(e: TokenError) {
+    match e {
+        TokenError::UnsupportedModel(machine_id) => {
+            eprintln!("Unknown/unsupported calculator: {machine_id:#04x}");
+        }
+        TokenError::NotAProgram(var_type) => {
+            eprintln!("Only programs can be saved as text, not variables of type {var_type:#04x}");
+        }
+        TokenError::Truncated => {
+            eprintln!("Program is cut short");
+        }
+        TokenError::Tokenized => {
+            eprintln!(
+                "Program has been run since it was last edited, so its source is gone. Open it in the Program Editor and \
+                 leave again to get it back"
+            );
+        }
+        TokenError::UnknownChar(c) => {
+            eprintln!("The calculator has no way of writing {c:?}");
+        }
+        TokenError::TooLong => {
+            eprintln!("Program is too big for the calculator");
+        }
+    }
+}
+
+fn probe_calc(link: &mut dyn LinkTransport) -> Result<u8, ()> {
+    match probe_model(link) {
+        Ok(machine_id) => Ok(machine_id),
+        Err(e) => {
+            eprintln!("Couldn't reach calculator. Is it turned on and at the home screen?");
+            print_transfer_error(e);
+            Err(())
+        }
+    }
+}
+
+// installs and/or starts soyuz, as asked. Only works with the calculator sitting at its home screen, i.e. before soyuz
+// is running
+fn prepare_soyuz(
+    link: &mut dyn LinkTransport,
+    soyuz_dir: Option<&Path>,
+    launch_soyuz: bool,
+) -> Result<(), ()> {
+    if soyuz_dir.is_none() && !launch_soyuz {
+        return Ok(());
+    }
+
+    eprintln!("Checking i68soyuz on calculator...");
+
+    let machine_id = probe_calc(link)?;
+    let model = model_name(machine_id).unwrap_or("unknown");
+
+    let bundle = match soyuz_dir {
+        Some(soyuz_dir) => Some(install_soyuz(link, soyuz_dir, machine_id)?),
+        None => None,
+    };
+
+    if !launch_soyuz {
+        println!("Start i68soyuz on calculator");
+        return Ok(());
+    }
+
+    match launch(link, machine_id, bundle.as_ref()) {
+        Ok(()) => {
+            eprintln!("i68soyuz launched on {model}\n");
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Launching i68soyuz failed\n");
+            match e {
+                LaunchError::NotInstalled => {
+                    eprintln!("i68soyuz isn't on the {model}. Try --soyuz-dir");
+                }
+                LaunchError::TransferError(e) => {
+                    print_transfer_error(e);
+                }
+            }
+            Err(())
+        }
+    }
+}
+
+// what was installed, so it's that copy that gets launched
+fn install_soyuz(
+    link: &mut dyn LinkTransport,
+    soyuz_dir: &Path,
+    machine_id: u8,
+) -> Result<TiVar, ()> {
+    let model = model_name(machine_id).unwrap_or("unknown");
+
+    let bundle = match load_bundle(soyuz_dir, machine_id) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            match e {
+                BundleError::UnsupportedModel(_) => {
+                    eprintln!("There's no i68soyuz for the {model}");
+                }
+                BundleError::ReadFailed(e) => {
+                    eprintln!(
+                        "Couldn't read i68soyuz for the {model} from {}. Reason: {e}",
+                        soyuz_dir.display()
+                    );
+                }
+                BundleError::BadFile(e) => {
+                    eprintln!("i68soyuz file for the {model} is damaged. Reason: {e:?}");
+                }
+                BundleError::WrongModel(file_machine_id) => {
+                    eprintln!(
+                        "i68soyuz file for the {model} is actually for the {}",
+                        model_name(file_machine_id).unwrap_or("unknown")
+                    );
+                }
+            }
+            return Err(());
+        }
+    };
+
+    match install(link, machine_id, &bundle) {
+        Ok(InstallOutcome::AlreadyInstalled) => {
+            eprintln!("i68soyuz on {model} is up to date\n");
+        }
+        Ok(InstallOutcome::Installed) => {
+            eprintln!("i68soyuz installed on {model}\n");
+        }
+        Ok(InstallOutcome::Updated) => {
+            eprintln!("i68soyuz on {model} updated\n");
+        }
+        Err(e) => {
+            eprintln!("Installing i68soyuz failed\n");
+            print_transfer_error(e);
+            return Err(());
+        }
+    }
+
+    Ok(bundle)
+}
+
+fn init_calc(link: &mut dyn LinkTransport, requested: &SessionConfig) -> Result<I68MetaInfo, ()> {
     println!("Press any key on calculator to continue");
+
+    await_handshake(link, requested)
+}
+
+fn await_handshake(
+    link: &mut dyn LinkTransport,
+    requested: &SessionConfig,
+) -> Result<I68MetaInfo, ()> {
     eprintln!("Waiting for handshake...");
 
-    let i68_config = match I68MetaInfo::handshake(cable) {
+    let i68_config = match I68MetaInfo::handshake_with(link, requested) {
         Ok(conf) => conf,
 
         Err(e) => {
@@ -102,6 +375,22 @@ fn init_calc(cable: &mut Cable) -> Result<I68MetaInfo, ()> {
                     eprintln!("Are you running the current version of i68apollo?");
                 }
 
+                HandshakeError::NoSuchKey(key) => {
+                    eprintln!("There's no {key:?} key on this calculator to quit with");
+                }
+
+                HandshakeError::BadConfig(config) => {
+                    eprintln!("i68soyuz answered the configuration with nonsense: {config:?}");
+                }
+
+                HandshakeError::LinkError(e) => {
+                    print_link_error(e);
+                }
+
+                HandshakeError::Interrupted => {
+                    eprintln!("Gave up waiting for i68soyuz");
+                }
+
                 HandshakeError::OtherError => {
                     eprintln!("Error during handshake");
                 }
@@ -118,7 +407,839 @@ fn init_calc(cable: &mut Cable) -> Result<I68MetaInfo, ()> {
     return Ok(i68_config);
 }
 
+fn init_watcher() -> Option<CableWatcher> {
+    match CableWatcher::new() {
+        Ok(watcher) => Some(watcher),
+
+        Err(e) => {
+            match e {
+                CableWatcherCreationError::HotplugUnsupported => {
+                    eprintln!("libusb has no hotplug support on this system.");
+                }
+                CableWatcherCreationError::RegisterFailed(e) => {
+                    eprintln!("Couldn't register hotplug callback. Reason: {e}");
+                }
+            }
+            eprintln!("Falling back to polling for the SilverLink if it gets unplugged\n");
+            None
+        }
+    }
+}
+
+// None if apollo's shutting down instead
+fn wait_for_cable(watcher: Option<&CableWatcher>, selector: &CableSelector) -> Option<Cable> {
+    eprintln!("Waiting for SilverLink to be plugged back in...");
+
+    // it may already be back, its arrival having gone by along with it leaving
+    if let Ok(cable) = Cable::open(selector) {
+        eprintln!("SilverLink successfully initialized\n");
+        return Some(cable);
+    }
+
+    loop {
+        // checking in every second or so, in case the wait's been called off
+        let arrived = match watcher {
+            Some(watcher) => watcher.wait_for_arrival(Duration::from_secs(1)),
+            None => {
+                thread::sleep(Duration::from_secs(1));
+                true
+            }
+        };
+        if shutdown::requested() {
+            return None;
+        }
+        if !arrived {
+            continue;
+        }
+
+        // a freshly plugged in cable can take a moment before it'll let us open it
+        for _ in 0..10 {
+            if let Ok(cable) = Cable::open(selector) {
+                eprintln!("SilverLink successfully initialized\n");
+                if let Some(watcher) = watcher {
+                    watcher.cable_left();
+                }
+                return Some(cable);
+            }
+            thread::sleep(Duration::from_millis(500));
+        }
+    }
+}
+
+struct SessionEnd {
+    time_elapsed: Duration,
+    link_error: Option<LinkIoError>,
+}
+
+fn session(
+    link: &mut dyn LinkTransport,
+    virtual_kbd: &mut VirtualKeyboard,
+    soyuz_launched: bool,
+    requested: &SessionConfig,
+    link_timeout: Duration,
+) -> Result<SessionEnd, ()> {
+    // no need to have the user press anything if we started soyuz ourselves
+    let mut calc = if soyuz_launched {
+        await_handshake(link, requested)?
+    } else {
+        init_calc(link, requested)?
+    };
+
+    // ---------------main loop---------------
+
+    eprintln!("Begin async key matrix data transfer");
+
+    println!("Press {:?} at any time to quit.\n", calc.config.quit_key);
+    let loop_start = Instant::now();
+    let link_error = run_until_done(
+        link,
+        calc.calc_handle.as_mut(),
+        virtual_kbd,
+        &run_config(&calc.config, link_timeout),
+    );
+    if shutdown::requested() {
+        tell_soyuz_to_exit(link, &calc);
+    }
+
+    Ok(SessionEnd {
+        time_elapsed: Instant::now().duration_since(loop_start),
+        link_error,
+    })
+}
+
+// keeps going through transient link errors, returns whatever error it finally gave up on, if any
+fn run_until_done(
+    link: &mut dyn LinkTransport,
+    calc: &mut dyn CalcHandle,
+    virtual_kbd: &mut dyn KeySink,
+    run_config: &RunConfig,
+) -> Option<LinkIoError> {
+    while let Err(e) = run(link, calc, virtual_kbd, run_config) {
+        if e.is_retryable() {
+            print_link_error(e);
+            eprintln!("Retrying...");
+            continue;
+        }
+
+        eprintln!("Key matrix data transfer aborted\n");
+        print_link_error(e);
+        return Some(e);
+    }
+
+    None
+}
+
+// so the calculator isn't left stuck in soyuz after apollo's gone
+fn tell_soyuz_to_exit(link: &mut dyn LinkTransport, calc: &I68MetaInfo) {
+    match calc.request_exit(link) {
+        Ok(true) => {
+            eprintln!("Told i68soyuz to exit");
+        }
+        Ok(false) => {
+            eprintln!(
+                "This i68soyuz can't be told to exit. Press {:?} on the calculator to quit it",
+                calc.config.quit_key
+            );
+        }
+        Err(e) => {
+            eprintln!("Couldn't tell i68soyuz to exit");
+            print_link_error(e);
+        }
+    }
+}
+
+fn run_config(config: &SessionConfig, link_timeout: Duration) -> RunConfig {
+    RunConfig {
+        quit_key: config.quit_key,
+        // a soyuz that doesn't send heartbeats goes quiet whenever nobody's typing, so there's no telling when it's gone
+        link_timeout: (config.flags & FLAG_HEARTBEAT != 0).then_some(link_timeout),
+        ..RunConfig::default()
+    }
+}
+
+// one calculator out of several. Everything but the quit prompt is the same as for a single calculator, just on its own
+// thread
+fn calc_thread(
+    cable: &mut Cable,
+    shared_kbd: Option<Arc<Mutex<MergedKeyboard>>>,
+    soyuz_dir: Option<&Path>,
+    launch_soyuz: bool,
+    requested: &SessionConfig,
+    link_timeout: Duration,
+) -> Result<SessionEnd, ()> {
+    let port_path = cable.info().port_path();
+
+    prepare_soyuz(cable, soyuz_dir, launch_soyuz)?;
+
+    let mut calc = await_handshake(cable, requested)?;
+    let model_name = model_name(calc.machine_id).unwrap_or("unknown");
+    eprintln!(
+        "{model_name} connected at {port_path}. Press {:?} on it at any time to disconnect it\n",
+        calc.config.quit_key
+    );
+
+    let mut virtual_kbd: Box<dyn KeySink> = match shared_kbd {
+        Some(shared_kbd) => Box::new(SharedKeyboard::new(shared_kbd)),
+        None => Box::new(init_vkbd(&format!(
+            "i68apollo ({model_name} @ {port_path})"
+        ))?),
+    };
+
+    let loop_start = Instant::now();
+    let link_error = run_until_done(
+        cable,
+        calc.calc_handle.as_mut(),
+        virtual_kbd.as_mut(),
+        &run_config(&calc.config, link_timeout),
+    );
+    if shutdown::requested() {
+        tell_soyuz_to_exit(cable, &calc);
+    }
+    eprintln!("{model_name} at {port_path} finished\n");
+
+    Ok(SessionEnd {
+        time_elapsed: Instant::now().duration_since(loop_start),
+        link_error,
+    })
+}
+
+fn multi_session(
+    selectors: Vec<CableSelector>,
+    merge_keyboards: bool,
+    soyuz_dir: Option<PathBuf>,
+    launch_soyuz: bool,
+    requested: SessionConfig,
+    link_timeout: Duration,
+) -> Result<(), ()> {
+    let mut cables = Vec::new();
+    for selector in &selectors {
+        cables.push(init_cable(selector)?);
+    }
+
+    let shared_kbd = if merge_keyboards {
+        let virtual_kbd = init_vkbd("i68apollo (composite)")?;
+        Some(Arc::new(Mutex::new(MergedKeyboard::new(Box::new(
+            virtual_kbd,
+        )))))
+    } else {
+        None
+    };
+
+    if !launch_soyuz {
+        println!("Press any key on each calculator to continue\n");
+    }
+
+    let calc_threads = cables
+        .into_iter()
+        .map(|mut cable| {
+            let shared_kbd = shared_kbd.clone();
+            let soyuz_dir = soyuz_dir.clone();
+            thread::spawn(move || {
+                if let Ok(session_end) = calc_thread(
+                    &mut cable,
+                    shared_kbd,
+                    soyuz_dir.as_deref(),
+                    launch_soyuz,
+                    &requested,
+                    link_timeout,
+                ) {
+                    println!(
+                        "{}:\n{}",
+                        cable.info().port_path(),
+                        stats_summary(cable.stats(), session_end.time_elapsed)
+                    );
+                }
+                let _ = cable.release();
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for calc_thread in calc_threads {
+        let _ = calc_thread.join();
+    }
+
+    Ok(())
+}
+
+fn vars_command(
+    selector: &CableSelector,
+    command: VarsCommand,
+    program_text: bool,
+) -> Result<(), ()> {
+    let mut cable = init_cable(selector)?;
+    let machine_id = probe_calc(&mut cable)?;
+
+    let result = match command {
+        VarsCommand::List => list_vars(&mut cable, machine_id).map(|entries| {
+            for entry in entries {
+                let name = if entry.folder.is_empty() {
+                    entry.name
+                } else {
+                    format!("{}\\{}", entry.folder, entry.name)
+                };
+                let flags = match entry.attr {
+                    attr if is_archived(machine_id, attr) => "archived",
+                    TI89_ATTR_LOCKED if machine_id != MACHINE_ID_TI83P => "locked",
+                    _ => "",
+                };
+                println!(
+                    "{:<18} {:<6} {:>6}  {}",
+                    name,
+                    type_name(machine_id, entry.var_type).unwrap_or("?"),
+                    entry.size,
+                    flags
+                );
+            }
+        }),
+
+        // the calculator's done with once the variable's off it
+        VarsCommand::Get(name) => {
+            let var = find_var(&mut cable, machine_id, &name)
+                .and_then(|entry| get_var(&mut cable, machine_id, &entry));
+            let _ = cable.release();
+
+            return var
+                .map_err(print_transfer_error)
+                .and_then(|var| save_var(machine_id, &name, var, program_text));
+        }
+
+        VarsCommand::Put(path) => {
+            let vars = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("txt") => read_program_source(&path, machine_id)?,
+                _ => read_var_file(&path, machine_id)?,
+            };
+
+            // a group file goes over one variable at a time
+            vars.iter().try_for_each(|var| {
+                send_var(&mut cable, machine_id, var)
+                    .map(|()| println!("Sent {} from {}", var.name, path.display()))
+            })
+        }
+
+        VarsCommand::Remove(name) => find_var(&mut cable, machine_id, &name)
+            .and_then(|entry| delete_var(&mut cable, machine_id, &entry))
+            .map(|()| println!("Deleted {name}")),
+    };
+
+    let _ = cable.release();
+
+    result.map_err(|e| {
+        print_transfer_error(e);
+    })
+}
+
+// a program that's lost its source on the 89/92+ is saved as the variable file instead, which can still be put back
+fn save_var(machine_id: u8, name: &str, var: TiVar, program_text: bool) -> Result<(), ()> {
+    let source = if program_text {
+        match program_to_source(machine_id, &var) {
+            Ok(source) => Some(source),
+            Err(TokenError::Tokenized) => {
+                print_token_error(TokenError::Tokenized);
+                eprintln!("Saving {name} as a variable file instead");
+                None
+            }
+            Err(e) => {
+                print_token_error(e);
+                return Err(());
+            }
+        }
+    } else {
+        None
+    };
+    let (file_name, bytes) = match source {
+        Some(source) => (format!("{}.txt", var.name), source.into_bytes()),
+        None => {
+            let file = VarFile::single(machine_id, "Saved by i68apollo", var);
+            (file.file_name(), file.to_bytes())
+        }
+    };
+
+    match fs::write(&file_name, bytes) {
+        Ok(()) => {
+            println!("Saved {name} to {file_name}");
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Couldn't write {file_name}. Reason: {e}");
+            Err(())
+        }
+    }
+}
+
+fn read_var_file(path: &Path, machine_id: u8) -> Result<Vec<TiVar>, ()> {
+    let file = match fs::read(path).map(|bytes| VarFile::parse(&bytes)) {
+        Ok(Ok(file)) => file,
+        Ok(Err(e)) => {
+            eprintln!("{} isn't a variable file. Reason: {e:?}", path.display());
+            return Err(());
+        }
+        Err(e) => {
+            eprintln!("Couldn't read {}. Reason: {e}", path.display());
+            return Err(());
+        }
+    };
+    // the 89 and 92+ can swap most variables, the 83+ has nothing in common with either
+    if (file.machine_id == MACHINE_ID_TI83P) != (machine_id == MACHINE_ID_TI83P) {
+        eprintln!(
+            "{} is for the {}, not the {}",
+            path.display(),
+            model_name(file.machine_id).unwrap_or("unknown"),
+            model_name(machine_id).unwrap_or("unknown")
+        );
+        return Err(());
+    }
+
+    Ok(file.vars)
+}
+
+// the program's named after the file, uppercased on the 83+ since that's all it allows
+fn read_program_source(path: &Path, machine_id: u8) -> Result<Vec<TiVar>, ()> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Couldn't read {}. Reason: {e}", path.display());
+            return Err(());
+        }
+    };
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    let name = match machine_id {
+        MACHINE_ID_TI83P => stem.to_uppercase(),
+        _ => stem.to_string(),
+    };
+
+    match source_to_program(machine_id, "main", &name, &source) {
+        Ok(var) => Ok(vec![var]),
+        Err(e) => {
+            print_token_error(e);
+            Err(())
+        }
+    }
+}
+
+fn screenshot_command(selector: &CableSelector, path: &Path) -> Result<(), ()> {
+    let mut cable = init_cable(selector)?;
+    let machine_id = probe_calc(&mut cable)?;
+
+    let result = screenshot(&mut cable, machine_id);
+    let _ = cable.release();
+
+    let screen = result.map_err(|e| {
+        print_transfer_error(e);
+    })?;
+    // the extension's already been checked to be one of these
+    let bytes = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("pbm") => screen.to_pbm(),
+        _ => screen.to_png(),
+    };
+
+    match fs::write(path, bytes) {
+        Ok(()) => {
+            println!(
+                "Saved {}x{} screenshot to {}",
+                screen.width,
+                screen.height,
+                path.display()
+            );
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Couldn't write {}. Reason: {e}", path.display());
+            Err(())
+        }
+    }
+}
+
+// redraws in place, only when something changed. There's no way out but ^C, which is fine since nothing here needs
+// cleaning up on the calculator's end
+fn mirror_command(selector: &CableSelector, style: RenderStyle, fps: u32) -> Result<(), ()> {
+    let mut cable = init_cable(selector)?;
+    let machine_id = probe_calc(&mut cable)?;
+
+    let frame_time = Duration::from_secs(1) / fps;
+    let mut last_screen = None;
+
+    // clear the terminal once up front, after that just move back to the top left and draw over it
+    print!("\x1b[2J");
+    loop {
+        let started = Instant::now();
+
+        let screen = match screenshot(&mut cable, machine_id) {
+            Ok(screen) => screen,
+            Err(e) => {
+                let _ = cable.release();
+                print_transfer_error(e);
+                return Err(());
+            }
+        };
+        if last_screen.as_ref() != Some(&screen) {
+            println!("\x1b[H{}", screen.render(style));
+            last_screen = Some(screen);
+        }
+
+        if let Some(remaining) = frame_time.checked_sub(started.elapsed()) {
+            thread::sleep(remaining);
+        }
+    }
+}
+
+fn backup_command(selector: &CableSelector, path: &Path) -> Result<(), ()> {
+    let mut cable = init_cable(selector)?;
+    let machine_id = probe_calc(&mut cable)?;
+
+    let result = backup(&mut cable, machine_id);
+    let _ = cable.release();
+
+    let backup = result.map_err(|e| {
+        print_transfer_error(e);
+    })?;
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs())
+        .unwrap_or(0);
+
+    if let Err(e) = fs::write(path, backup.to_tar(mtime)) {
+        eprintln!("Couldn't write {}. Reason: {e}", path.display());
+        return Err(());
+    }
+
+    println!(
+        "Backed up {} variables to {}",
+        backup.vars.len(),
+        path.display()
+    );
+    for app in &backup.apps {
+        println!("FlashApp {} is listed, but not backed up", app.name);
+    }
+
+    Ok(())
+}
+
+fn restore_command(selector: &CableSelector, path: &Path) -> Result<(), ()> {
+    // read the backup before touching the calculator, in case there's something wrong with it
+    let backup = match fs::read(path).map(|bytes| Backup::from_tar(&bytes)) {
+        Ok(Ok(backup)) => backup,
+        Ok(Err(e)) => {
+            eprintln!("{} isn't a backup. Reason: {e:?}", path.display());
+            return Err(());
+        }
+        Err(e) => {
+            eprintln!("Couldn't read {}. Reason: {e}", path.display());
+            return Err(());
+        }
+    };
+
+    let mut cable = init_cable(selector)?;
+    let machine_id = probe_calc(&mut cable)?;
+
+    if (backup.machine_id == MACHINE_ID_TI83P) != (machine_id == MACHINE_ID_TI83P) {
+        eprintln!(
+            "The backup is from a {}, not a {}",
+            model_name(backup.machine_id).unwrap_or("unknown"),
+            model_name(machine_id).unwrap_or("unknown")
+        );
+        let _ = cable.release();
+        return Err(());
+    }
+
+    let results = restore(&mut cable, machine_id, &backup);
+    let _ = cable.release();
+
+    let mut failed = 0;
+    for (var, result) in backup.vars.iter().zip(results) {
+        match result {
+            Ok(()) => println!("Restored {}", var.name),
+            Err(e) => {
+                eprintln!("Couldn't restore {}", var.name);
+                print_transfer_error(e);
+                failed += 1;
+            }
+        }
+    }
+    for app in &backup.apps {
+        println!("FlashApp {} has to be reinstalled by hand", app.name);
+    }
+
+    match failed {
+        0 => Ok(()),
+        _ => {
+            eprintln!(
+                "{failed} of {} variables weren't restored",
+                backup.vars.len()
+            );
+            Err(())
+        }
+    }
+}
+
+// KEY_PAUSE, for getting the keyboard back from `remote`
+const REMOTE_QUIT_KEY: u16 = 119;
+
+fn clock_command(selector: &CableSelector, command: ClockCommand) -> Result<(), ()> {
+    let mut cable = init_cable(selector)?;
+    let machine_id = probe_calc(&mut cable)?;
+
+    if machine_id != MACHINE_ID_TI89 {
+        eprintln!(
+            "The {} has no clock that can be set over the link",
+            model_name(machine_id).unwrap_or("unknown calculator")
+        );
+        let _ = cable.release();
+        return Err(());
+    }
+
+    let result = get_clock(&mut cable, machine_id).and_then(|clock| {
+        println!("Calculator's clock is {}", describe_drift(&clock));
+        if command == ClockCommand::Show {
+            return Ok(());
+        }
+
+        let synced = CalcClock {
+            seconds: (local_now() - CLOCK_EPOCH) as u32,
+            ..clock
+        };
+        set_clock(&mut cable, machine_id, &synced)?;
+        let clock = get_clock(&mut cable, machine_id)?;
+        println!(
+            "Set the calculator's clock, it's now {}",
+            describe_drift(&clock)
+        );
+
+        Ok(())
+    });
+
+    let _ = cable.release();
+
+    result.map_err(|e| match e {
+        // what a plain 89 (as opposed to a Titanium) says when asked for the time
+        TransferError::Refused | TransferError::NotFound => {
+            eprintln!("Calculator has no clock. Only the 89 Titanium does");
+        }
+        e => print_transfer_error(e),
+    })
+}
+
+// the computer's time, in the local time zone since that's what the calculator keeps
+fn local_now() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() as i64)
+        .unwrap_or(0);
+
+    let mut tm = unsafe { std::mem::zeroed::<libc::tm>() };
+    let utc_offset = if unsafe { libc::localtime_r(&now, &mut tm) }.is_null() {
+        0
+    } else {
+        tm.tm_gmtoff
+    };
+
+    now + utc_offset
+}
+
+fn describe_drift(clock: &CalcClock) -> String {
+    match CLOCK_EPOCH + clock.seconds as i64 - local_now() {
+        0 => "in step with this computer's".to_string(),
+        drift if drift > 0 => format!("{drift}s ahead of this computer's"),
+        drift => format!("{}s behind this computer's", -drift),
+    }
+}
+
+fn remote_command(selector: &CableSelector, input: RemoteInput) -> Result<(), ()> {
+    let mut cable = init_cable(selector)?;
+    let machine_id = probe_calc(&mut cable)?;
+
+    let result = match input {
+        RemoteInput::Stdin => remote_from_stdin(&mut cable, machine_id),
+        RemoteInput::Evdev(path) => remote_from_evdev(&mut cable, machine_id, &path),
+    };
+
+    let _ = cable.release();
+
+    result
+}
+
+fn remote_from_stdin(link: &mut dyn LinkTransport, machine_id: u8) -> Result<(), ()> {
+    for line in io::stdin().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Couldn't read stdin. Reason: {e}");
+                return Err(());
+            }
+        };
+
+        for c in line.chars().chain(['\n']) {
+            let Some(keycode) = char_key(machine_id, c) else {
+                eprintln!("No key for {c:?}, skipping it");
+                continue;
+            };
+            send_key(link, machine_id, keycode).map_err(|e| {
+                print_transfer_error(e);
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+fn remote_from_evdev(link: &mut dyn LinkTransport, machine_id: u8, path: &Path) -> Result<(), ()> {
+    let mut keyboard = match EvdevKeyboard::open(path) {
+        Ok(keyboard) => keyboard,
+        Err(e) => {
+            eprintln!("Couldn't open {}. Reason: {e}", path.display());
+            return Err(());
+        }
+    };
+    let mut remote_kbd = RemoteKeyboard::new(machine_id);
+
+    println!("Typing on the calculator, press PAUSE to stop");
+    loop {
+        let (code, pressed) = match keyboard.next_key() {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("Couldn't read {}. Reason: {e}", path.display());
+                return Err(());
+            }
+        };
+        if code == REMOTE_QUIT_KEY {
+            return Ok(());
+        }
+
+        let Some(keycode) = remote_kbd
+            .calc_key(code)
+            .and_then(|key| remote_kbd.key_event(key, pressed))
+        else {
+            continue;
+        };
+        send_key(link, machine_id, keycode).map_err(|e| {
+            print_transfer_error(e);
+        })?;
+    }
+}
+
+fn stats_summary(stats: &LinkStats, time_elapsed: Duration) -> String {
+    let secs = time_elapsed.as_secs_f64();
+    let per_sec = |count: f64| if secs > 0.0 { count / secs } else { 0.0 };
+    let millis = |duration: Duration| duration.as_secs_f64() * 1000.0;
+
+    let mut summary = format!(
+        "{} overall bytes read in {:.2} seconds ({:.1} bytes/s)\n\
+         {} frames read ({:.1} frames/s), {} coalesced\n\
+         overreads: {}, malformed reads: {}",
+        stats.bytes_read_overall,
+        secs,
+        per_sec(stats.bytes_read_overall as f64),
+        stats.frames_read,
+        per_sec(stats.frames_read as f64),
+        stats.frames_coalesced,
+        stats.overreads,
+        stats.malformed_reads,
+    );
+
+    if let (Some(min), Some(mean)) = (stats.latency.min, stats.latency.mean()) {
+        summary += &format!(
+            "\nlatency (link to keyboard): min {:.2} ms, mean {:.2} ms, max {:.2} ms",
+            millis(min),
+            millis(mean),
+            millis(stats.latency.max),
+        );
+    }
+
+    summary
+}
+
 fn main() -> Result<(), ()> {
+    let args = match ApolloArgs::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            match e {
+                ArgsError::HelpRequested => {}
+                ArgsError::MissingValue(arg) => {
+                    eprintln!("{arg} needs a value\n");
+                }
+                ArgsError::InvalidValue(arg, value) => {
+                    eprintln!("Invalid value {value} for {arg}\n");
+                }
+                ArgsError::UnknownArgument(arg) => {
+                    eprintln!("Unknown argument {arg}\n");
+                }
+            }
+            eprintln!("{}", usage());
+            return Err(());
+        }
+    };
+
+    if args.list_cables {
+        return list_cables();
+    }
+
+    if let Some(command) = args.vars_command {
+        return match args.link {
+            LinkChoice::SilverLink(selector) => vars_command(&selector, command, args.program_text),
+            _ => {
+                eprintln!("vars only works with a single SilverLink cable");
+                Err(())
+            }
+        };
+    }
+
+    if let Some(command) = args.backup_command {
+        return match args.link {
+            LinkChoice::SilverLink(selector) => match command {
+                BackupCommand::Backup(path) => backup_command(&selector, &path),
+                BackupCommand::Restore(path) => restore_command(&selector, &path),
+            },
+            _ => {
+                eprintln!("backup and restore only work with a single SilverLink cable");
+                Err(())
+            }
+        };
+    }
+
+    if let Some(command) = args.clock_command {
+        return match args.link {
+            LinkChoice::SilverLink(selector) => clock_command(&selector, command),
+            _ => {
+                eprintln!("clock only works with a single SilverLink cable");
+                Err(())
+            }
+        };
+    }
+
+    if let Some(input) = args.remote_input {
+        return match args.link {
+            LinkChoice::SilverLink(selector) => remote_command(&selector, input),
+            _ => {
+                eprintln!("type and remote only work with a single SilverLink cable");
+                Err(())
+            }
+        };
+    }
+
+    if args.mirror {
+        return match args.link {
+            LinkChoice::SilverLink(selector) => {
+                mirror_command(&selector, args.mirror_style, args.mirror_fps)
+            }
+            _ => {
+                eprintln!("mirror only works with a single SilverLink cable");
+                Err(())
+            }
+        };
+    }
+
+    if let Some(path) = args.screenshot {
+        return match args.link {
+            LinkChoice::SilverLink(selector) => screenshot_command(&selector, &path),
+            _ => {
+                eprintln!("screenshot only works with a single SilverLink cable");
+                Err(())
+            }
+        };
+    }
+
     // ---------------startup message---------------
 
     let (apollo_ver_major, apollo_ver_minor, apollo_ver_patch) = apollo_version();
@@ -130,32 +1251,133 @@ fn main() -> Result<(), ()> {
 
     // ---------------init---------------
 
-    let mut cable = init_cable()?;
+    // from here on Ctrl-C winds the session down rather than leaving keys held and soyuz running
+    if let Err(e) = shutdown::install_handlers() {
+        eprintln!("Couldn't set up Ctrl-C handling, so it'll kill i68apollo outright. Reason: {e}");
+    }
 
-    let mut virtual_kbd = init_vkbd()?;
+    match args.link {
+        LinkChoice::SilverLinks(selectors) => {
+            multi_session(
+                selectors,
+                args.merge_keyboards,
+                args.soyuz_dir,
+                args.launch_soyuz,
+                args.session_config,
+                args.link_timeout,
+            )?;
+        }
 
-    let calc = init_calc(&mut cable)?;
+        LinkChoice::AllSilverLinks => {
+            let selectors = match Cable::list() {
+                Ok(cables) => cables
+                    .iter()
+                    .map(|info| CableSelector::PortPath(info.port_path()))
+                    .collect::<Vec<CableSelector>>(),
+                Err(_) => {
+                    eprintln!("Couldn't get USB devices list");
+                    return Err(());
+                }
+            };
+            if selectors.is_empty() {
+                eprintln!("Couldn't find any SilverLink cables. Are they plugged in?");
+                return Err(());
+            }
 
-    // ---------------main loop---------------
+            multi_session(
+                selectors,
+                args.merge_keyboards,
+                args.soyuz_dir,
+                args.launch_soyuz,
+                args.session_config,
+                args.link_timeout,
+            )?;
+        }
 
-    eprintln!("Begin async key matrix data transfer");
+        LinkChoice::SilverLink(selector) => {
+            let watcher = init_watcher();
 
-    println!("Press ON at any time to quit.\n");
-    let loop_start = Instant::now();
-    run(&mut cable, calc.calc_handle, &mut virtual_kbd);
+            let mut cable = init_cable(&selector)?;
 
-    // ---------------print stats---------------
+            prepare_soyuz(&mut cable, args.soyuz_dir.as_deref(), args.launch_soyuz)?;
 
-    let time_elapsed = Instant::now().duration_since(loop_start);
-    println!(
-        "{} overall bytes read in {:.2} seconds",
-        cable.stat_bytes_read_overall,
-        time_elapsed.as_secs_f64(),
-    );
-    println!(
-        "overreads: {}, malformed reads: {}",
-        cable.stat_overreads, cable.stat_malformed_reads
-    );
+            let mut virtual_kbd = init_vkbd("i68apollo")?;
+
+            let mut time_elapsed = Duration::ZERO;
+            let mut stats = LinkStats::default();
+            let mut soyuz_launched = args.launch_soyuz;
+            loop {
+                let session_end = match session(
+                    &mut cable,
+                    &mut virtual_kbd,
+                    soyuz_launched,
+                    &args.session_config,
+                    args.link_timeout,
+                ) {
+                    Ok(session_end) => session_end,
+                    Err(()) if shutdown::requested() => break,
+                    Err(()) => {
+                        return Err(());
+                    }
+                };
+                soyuz_launched = false;
+
+                time_elapsed += session_end.time_elapsed;
+                // taken rather than copied, so nothing's counted twice if the same cable goes round again
+                stats.merge(&mem::take(cable.stats_mut()));
+                if shutdown::requested() {
+                    break;
+                }
+
+                // soyuz is still sitting there on the calculator waiting for us, so once the cable's back all it
+                // takes is another handshake
+                let unplugged = match session_end.link_error {
+                    None => false,
+                    Some(LinkIoError::Disconnected) => true,
+                    Some(_) => watcher.as_ref().is_some_and(|watcher| watcher.cable_left()),
+                };
+                // the cable's fine but soyuz is gone. Once it's started again, it's another handshake over the same
+                // cable
+                if !unplugged && session_end.link_error == Some(LinkIoError::HeartbeatLost) {
+                    eprintln!("Waiting for i68soyuz to be started again...\n");
+                    continue;
+                }
+                if !unplugged {
+                    break;
+                }
+
+                let selector = selector.for_reconnect(cable.info());
+                cable = match wait_for_cable(watcher.as_ref(), &selector) {
+                    Some(cable) => cable,
+                    None => break,
+                };
+            }
+            let _ = cable.release();
+
+            // ---------------print stats---------------
+
+            println!("{}", stats_summary(&stats, time_elapsed));
+        }
+
+        LinkChoice::Socket(path) => {
+            let mut socket = init_socket(&path)?;
+
+            let mut virtual_kbd = init_vkbd("i68apollo")?;
+
+            let session_end = session(
+                &mut socket,
+                &mut virtual_kbd,
+                false,
+                &args.session_config,
+                args.link_timeout,
+            )?;
+
+            println!(
+                "{}",
+                stats_summary(socket.stats(), session_end.time_elapsed)
+            );
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,131 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{SyncSender, TrySendError},
+    },
+    time::{Duration, Instant},
+};
+
+use debug_print::debug_eprintln;
+
+use crate::{
+    calc::CalcHandle,
+    keyboard::CalcKey,
+    link::{LinkIoError, LinkTransport},
+    shutdown, RunConfig,
+};
+
+// how long a single read may block before the reader thread checks whether it's been told to stop. Timing out partway
+// through a report is fine: the transports hold on to what they've got of a read until the next one, and whatever
+// `get_keys` has already read of a report it keeps
+pub const READER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct KeyEvent {
+    pub key: CalcKey,
+    pub pressed: bool,
+    // when the frame carrying this event came off the link
+    pub timestamp: Instant,
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum LinkEvent {
+    // every change from one key matrix frame, or several merged frames, see `OverflowPolicy::Coalesce`
+    Frame(Vec<KeyEvent>),
+    // the reader gives up after sending one of these
+    Error(LinkIoError),
+}
+
+// what the reader does when the output side has fallen behind and the channel is full. There's deliberately no policy
+// that just throws frames away: frames only carry changes, so a dropped release would leave a key stuck down
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum OverflowPolicy {
+    // wait for room. The link keeps buffering on its own end in the meantime
+    Block,
+    // keep reading, and hand over everything that piled up as one bigger frame once there's room again
+    Coalesce,
+}
+
+// pulls frames off the link and decodes them until told to stop or the link fails. Meant to be run on its own thread
+// so that a slow uinput write never holds up a USB read, and vice versa. With a `link_timeout`, going that long without
+// a single frame counts as the link failing. Shutting down hangs up on the other end of `events` without an error.
+// Nothing after the quit key gets read, since soyuz is done by then and whatever follows is the next session's
+pub fn read_link(
+    link: &mut dyn LinkTransport,
+    calc: &mut dyn CalcHandle,
+    events: SyncSender<LinkEvent>,
+    stop: &AtomicBool,
+    config: &RunConfig,
+) -> u64 {
+    let mut pending: Vec<KeyEvent> = Vec::new();
+    let mut frames_coalesced = 0;
+    let mut last_frame = Instant::now();
+
+    while !stop.load(Ordering::Relaxed) && !shutdown::requested() {
+        let result = match calc.get_keys(link, READER_POLL_INTERVAL) {
+            Err(LinkIoError::Timeout)
+                if config
+                    .link_timeout
+                    .is_some_and(|link_timeout| last_frame.elapsed() >= link_timeout) =>
+            {
+                Err(LinkIoError::HeartbeatLost)
+            }
+            result => result,
+        };
+
+        let keys = match result {
+            Ok(keys) => {
+                last_frame = Instant::now();
+                keys
+            }
+            // nothing new, but there might still be coalesced events waiting for room
+            Err(LinkIoError::Timeout) => Vec::new(),
+            Err(e) => {
+                debug_eprintln!("reader: link failed, stopping. Reason: {e:?}");
+                if !pending.is_empty() {
+                    let _ = events.send(LinkEvent::Frame(std::mem::take(&mut pending)));
+                }
+                let _ = events.send(LinkEvent::Error(e));
+                break;
+            }
+        };
+
+        let quit = keys
+            .iter()
+            .any(|(key, pressed)| *key == config.quit_key && *pressed);
+
+        let timestamp = Instant::now();
+        pending.extend(keys.into_iter().map(|(key, pressed)| KeyEvent {
+            key,
+            pressed,
+            timestamp,
+        }));
+        if quit {
+            let _ = events.send(LinkEvent::Frame(std::mem::take(&mut pending)));
+            break;
+        }
+        if pending.is_empty() {
+            continue;
+        }
+
+        let frame = LinkEvent::Frame(std::mem::take(&mut pending));
+        match config.overflow_policy {
+            OverflowPolicy::Block => {
+                if events.send(frame).is_err() {
+                    break;
+                }
+            }
+            OverflowPolicy::Coalesce => match events.try_send(frame) {
+                Ok(()) => {}
+                Err(TrySendError::Full(LinkEvent::Frame(frame))) => {
+                    debug_eprintln!("reader: output is behind, coalescing frame");
+                    frames_coalesced += 1;
+                    pending = frame;
+                }
+                Err(_) => break,
+            },
+        }
+    }
+
+    frames_coalesced
+}
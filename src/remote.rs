@@ -0,0 +1,345 @@
+use debug_print::debug_eprintln;
+
+use crate::{
+    calc::{MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P},
+    keyboard::{calc_keys_for_linux_key, CalcKey},
+    link::{
+        packet::{pc_machine_id, write_packet, Command, Packet},
+        LinkTransport,
+    },
+    vars::{expect, TransferError, TRANSFER_TIMEOUT},
+};
+
+pub mod evdev;
+
+// pressing keys on the calculator from over the link, same as TiLP's remote control. Like the silent transfers this is
+// handled by the OS, so only works when nothing else (i.e. soyuz) has the link
+
+// 89/92+ keycodes. Anything printable is just its ASCII code
+pub const TI89_KEY_ENTER: u16 = 13;
+pub const TI89_KEY_CLEAR: u16 = 263;
+pub const TI89_KEY_ESC: u16 = 264;
+pub const TI89_KEY_HOME: u16 = 277;
+// held modifiers get added on to the key they go with
+const TI89_MOD_2ND: u16 = 4096;
+const TI89_MOD_DIAMOND: u16 = 8192;
+const TI89_MOD_SHIFT: u16 = 16384;
+const TI89_MOD_HAND: u16 = 32768;
+
+// 83+ keycodes, the same ones `GetKey` hands back
+pub const TI83P_KEY_ENTER: u16 = 0x05;
+pub const TI83P_KEY_CLEAR: u16 = 0x09;
+pub const TI83P_KEY_QUIT: u16 = 0x40;
+pub const TI83P_KEY_0: u16 = 0x8E;
+pub const TI83P_KEY_CAP_A: u16 = 0x9A;
+// the "prgm" token, not the PRGM menu
+pub const TI83P_KEY_EXEC: u16 = 0xDA;
+pub const TI83P_KEY_ASM: u16 = 0xFC9C;
+
+pub fn send_key(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    keycode: u16,
+) -> Result<(), TransferError> {
+    send_key_no_wait(link, machine_id, keycode)?;
+    // the 83+ acknowledges once when it gets the key and again once it's done whatever the key does
+    if machine_id == MACHINE_ID_TI83P {
+        expect(link, Command::Ack)?;
+    }
+
+    Ok(())
+}
+
+// only waits for the calculator to get the key, not for it to be done with it. For keys that start something that
+// won't be done for a while, e.g. soyuz, and that has its own things to say over the link in the meantime
+pub fn send_key_no_wait(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    keycode: u16,
+) -> Result<(), TransferError> {
+    let pc_id = pc_machine_id(machine_id).ok_or(TransferError::UnsupportedModel(machine_id))?;
+
+    debug_eprintln!("remote: key {keycode:#06x}");
+
+    write_packet(
+        link,
+        &Packet::with_param(pc_id, Command::Cmd, keycode),
+        TRANSFER_TIMEOUT,
+    )?;
+    expect(link, Command::Ack)?;
+
+    Ok(())
+}
+
+pub fn send_keys(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    keycodes: &[u16],
+) -> Result<(), TransferError> {
+    for keycode in keycodes {
+        send_key(link, machine_id, *keycode)?;
+    }
+
+    Ok(())
+}
+
+// the keycode that types `c` at the 83+'s home screen, if there is one. Only covers what can go in a variable name
+pub fn ti83p_char_key(c: char) -> Option<u16> {
+    match c {
+        'A'..='Z' => Some(TI83P_KEY_CAP_A + (c as u16 - 'A' as u16)),
+        '0'..='9' => Some(TI83P_KEY_0 + (c as u16 - '0' as u16)),
+        _ => None,
+    }
+}
+
+// the keycode for `c` on any model, for typing out text. Newlines hit ENTER
+pub fn char_key(machine_id: u8, c: char) -> Option<u16> {
+    match (machine_id, c) {
+        (MACHINE_ID_TI83P, '\n') => Some(TI83P_KEY_ENTER),
+        (MACHINE_ID_TI83P, _) => ti83p_char_key(c).or_else(|| ti83p_symbol_key(c)),
+        (_, '\n') => Some(TI89_KEY_ENTER),
+        (_, ' '..='~') => Some(c as u16),
+        _ => None,
+    }
+}
+
+fn ti83p_symbol_key(c: char) -> Option<u16> {
+    match c {
+        ' ' => Some(0x99),
+        '+' => Some(0x80),
+        '-' => Some(0x81),
+        '*' => Some(0x82),
+        '/' => Some(0x83),
+        '^' => Some(0x84),
+        '(' => Some(0x85),
+        ')' => Some(0x86),
+        '[' => Some(0x87),
+        ']' => Some(0x88),
+        ',' => Some(0x8B),
+        '.' => Some(0x8D),
+        ':' => Some(0xC6),
+        '"' => Some(0xCB),
+        _ => None,
+    }
+}
+
+// the keycode for pressing `key` on its own, if the model has one. The 89 and 92+ share their codes, so either takes
+// keys the other's keyboard doesn't have
+pub fn calc_key_code(machine_id: u8, key: CalcKey) -> Option<u16> {
+    match machine_id {
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => ti89_key_code(key),
+        MACHINE_ID_TI83P => ti83p_key_code(key),
+        _ => None,
+    }
+}
+
+fn ti89_key_code(key: CalcKey) -> Option<u16> {
+    if let Some(c) = key_char(key) {
+        return Some(c.to_ascii_lowercase() as u16);
+    }
+
+    match key {
+        CalcKey::CursorUp => Some(337),
+        CalcKey::CursorLeft => Some(338),
+        CalcKey::CursorRight => Some(340),
+        CalcKey::CursorDown => Some(344),
+        CalcKey::BckSpc => Some(257),
+        CalcKey::STO => Some(258),
+        CalcKey::SIN => Some(259),
+        CalcKey::COS => Some(260),
+        CalcKey::TAN => Some(261),
+        CalcKey::LN => Some(262),
+        CalcKey::CLEAR => Some(TI89_KEY_CLEAR),
+        CalcKey::ESC => Some(TI89_KEY_ESC),
+        CalcKey::APPS => Some(265),
+        CalcKey::MODE => Some(266),
+        CalcKey::ON => Some(267),
+        CalcKey::F1 => Some(268),
+        CalcKey::F2 => Some(269),
+        CalcKey::F3 => Some(270),
+        CalcKey::F4 => Some(271),
+        CalcKey::F5 => Some(272),
+        CalcKey::F6 => Some(273),
+        CalcKey::F7 => Some(274),
+        CalcKey::F8 => Some(275),
+        CalcKey::HOME => Some(TI89_KEY_HOME),
+        CalcKey::CATLG => Some(278),
+        CalcKey::ENTER1 | CalcKey::ENTER2 => Some(TI89_KEY_ENTER),
+        // DEL is diamond + backspace
+        CalcKey::DEL => Some(TI89_MOD_DIAMOND + 257),
+        CalcKey::Theta => Some(136),
+        CalcKey::Negative => Some(173),
+        CalcKey::EE => Some(149),
+        CalcKey::Bar => Some(b'|' as u16),
+        CalcKey::Equals => Some(b'=' as u16),
+        CalcKey::LeftParenthesis => Some(b'(' as u16),
+        CalcKey::RightParenthesis => Some(b')' as u16),
+        CalcKey::Comma => Some(b',' as u16),
+        CalcKey::Addition => Some(b'+' as u16),
+        CalcKey::Subtraction => Some(b'-' as u16),
+        CalcKey::Multiplication => Some(b'*' as u16),
+        CalcKey::Division => Some(b'/' as u16),
+        CalcKey::Exponentiation => Some(b'^' as u16),
+        CalcKey::Period => Some(b'.' as u16),
+        CalcKey::Space => Some(b' ' as u16),
+        _ => None,
+    }
+}
+
+// the 83+ has no letter keys as such, but takes the codes for what ALPHA + key would type
+fn ti83p_key_code(key: CalcKey) -> Option<u16> {
+    if let Some(c) = key_char(key) {
+        return ti83p_char_key(c);
+    }
+
+    match key {
+        CalcKey::CursorRight => Some(0x01),
+        CalcKey::CursorLeft => Some(0x02),
+        CalcKey::CursorUp => Some(0x03),
+        CalcKey::CursorDown => Some(0x04),
+        CalcKey::ENTER1 => Some(TI83P_KEY_ENTER),
+        CalcKey::CLEAR => Some(TI83P_KEY_CLEAR),
+        CalcKey::DEL => Some(0x0A),
+        CalcKey::APPS => Some(0x2C),
+        CalcKey::PRGM => Some(0x2D),
+        CalcKey::STAT => Some(0x31),
+        CalcKey::MATH => Some(0x32),
+        CalcKey::VARS => Some(0x35),
+        CalcKey::MODE => Some(0x45),
+        // Y=, WINDOW, ZOOM, TRACE, GRAPH
+        CalcKey::F1 => Some(0x49),
+        CalcKey::F2 => Some(0x48),
+        CalcKey::F3 => Some(0x2E),
+        CalcKey::F4 => Some(0x5A),
+        CalcKey::F5 => Some(0x44),
+        CalcKey::Addition => Some(0x80),
+        CalcKey::Subtraction => Some(0x81),
+        CalcKey::Multiplication => Some(0x82),
+        CalcKey::Division => Some(0x83),
+        CalcKey::Exponentiation => Some(0x84),
+        CalcKey::LeftParenthesis => Some(0x85),
+        CalcKey::RightParenthesis => Some(0x86),
+        CalcKey::STO => Some(0x8A),
+        CalcKey::Comma => Some(0x8B),
+        CalcKey::Negative => Some(0x8C),
+        CalcKey::Period => Some(0x8D),
+        CalcKey::Space => Some(0x99),
+        CalcKey::XCommaTCommaThetaCommaN => Some(0xB4),
+        CalcKey::Inverse => Some(0xB6),
+        CalcKey::SIN => Some(0xB7),
+        CalcKey::COS => Some(0xB9),
+        CalcKey::TAN => Some(0xBB),
+        CalcKey::Square => Some(0xBD),
+        CalcKey::LN => Some(0xBF),
+        CalcKey::LOG => Some(0xC1),
+        CalcKey::Theta => Some(0xCC),
+        _ => None,
+    }
+}
+
+// the digit or (uppercase) letter on a key, if that's all it is
+fn key_char(key: CalcKey) -> Option<char> {
+    let c = match key {
+        CalcKey::_0 => '0',
+        CalcKey::_1 => '1',
+        CalcKey::_2 => '2',
+        CalcKey::_3 => '3',
+        CalcKey::_4 => '4',
+        CalcKey::_5 => '5',
+        CalcKey::_6 => '6',
+        CalcKey::_7 => '7',
+        CalcKey::_8 => '8',
+        CalcKey::_9 => '9',
+        CalcKey::A => 'A',
+        CalcKey::B => 'B',
+        CalcKey::C => 'C',
+        CalcKey::D => 'D',
+        CalcKey::E => 'E',
+        CalcKey::F => 'F',
+        CalcKey::G => 'G',
+        CalcKey::H => 'H',
+        CalcKey::I => 'I',
+        CalcKey::J => 'J',
+        CalcKey::K => 'K',
+        CalcKey::L => 'L',
+        CalcKey::M => 'M',
+        CalcKey::N => 'N',
+        CalcKey::O => 'O',
+        CalcKey::P => 'P',
+        CalcKey::Q => 'Q',
+        CalcKey::R => 'R',
+        CalcKey::S => 'S',
+        CalcKey::T => 'T',
+        CalcKey::U => 'U',
+        CalcKey::V => 'V',
+        CalcKey::W => 'W',
+        CalcKey::X => 'X',
+        CalcKey::Y => 'Y',
+        CalcKey::Z => 'Z',
+        _ => {
+            return None;
+        }
+    };
+
+    Some(c)
+}
+
+fn ti89_modifier(key: CalcKey) -> Option<u16> {
+    match key {
+        CalcKey::_2nd => Some(TI89_MOD_2ND),
+        CalcKey::Diamond => Some(TI89_MOD_DIAMOND),
+        CalcKey::Shift => Some(TI89_MOD_SHIFT),
+        CalcKey::Hand => Some(TI89_MOD_HAND),
+        _ => None,
+    }
+}
+
+// turns key presses and releases from a PC keyboard into keycodes to send. Modifiers don't get sent on their own, they
+// get added on to whatever's pressed while they're held. The 83+ doesn't do modifiers over the link, so there 2nd and
+// ALPHA are just dropped
+pub struct RemoteKeyboard {
+    machine_id: u8,
+    modifiers: u16,
+}
+impl RemoteKeyboard {
+    pub fn new(machine_id: u8) -> RemoteKeyboard {
+        RemoteKeyboard {
+            machine_id,
+            modifiers: 0,
+        }
+    }
+
+    // for a Linux keycode, whichever calculator key it stands for that this model can actually send
+    pub fn calc_key(&self, linux_code: u16) -> Option<CalcKey> {
+        calc_keys_for_linux_key(linux_code).into_iter().find(|key| {
+            calc_key_code(self.machine_id, *key).is_some()
+                || (self.machine_id != MACHINE_ID_TI83P && ti89_modifier(*key).is_some())
+        })
+    }
+
+    // the keycode to send for this, if anything
+    pub fn key_event(&mut self, key: CalcKey, pressed: bool) -> Option<u16> {
+        if self.machine_id != MACHINE_ID_TI83P {
+            if let Some(modifier) = ti89_modifier(key) {
+                if pressed {
+                    self.modifiers |= modifier;
+                } else {
+                    self.modifiers &= !modifier;
+                }
+                return None;
+            }
+        }
+        if !pressed {
+            return None;
+        }
+
+        let keycode = calc_key_code(self.machine_id, key)?;
+        match self.modifiers {
+            // shifted letters are just their capitals
+            TI89_MOD_SHIFT if (b'a' as u16..=b'z' as u16).contains(&keycode) => {
+                Some(keycode - b'a' as u16 + b'A' as u16)
+            }
+            modifiers => Some(keycode | modifiers),
+        }
+    }
+}
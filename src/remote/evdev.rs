@@ -0,0 +1,52 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    mem,
+    os::fd::AsRawFd,
+    path::Path,
+};
+
+use debug_print::debug_eprintln;
+
+// reading a real keyboard straight from /dev/input, for typing on the calculator from the PC
+
+const EV_KEY: u16 = 0x01;
+// _IOW('E', 0x90, int)
+const EVIOCGRAB: u32 = 0x4004_4590;
+
+pub struct EvdevKeyboard {
+    file: File,
+}
+impl EvdevKeyboard {
+    // grabs the device too, so what's typed only goes to the calculator and not also to whatever has focus. The grab
+    // goes away when this does
+    pub fn open(path: &Path) -> io::Result<EvdevKeyboard> {
+        let file = File::open(path)?;
+
+        if unsafe { libc::ioctl(file.as_raw_fd(), EVIOCGRAB as _, 1) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(EvdevKeyboard { file })
+    }
+
+    // blocks until a key goes down or comes up, giving its Linux keycode and whether it's down. Autorepeats count as
+    // presses
+    pub fn next_key(&mut self) -> io::Result<(u16, bool)> {
+        // [time, type, code, value], the time being however big a timeval is here
+        let mut event = [0u8; mem::size_of::<libc::input_event>()];
+        loop {
+            self.file.read_exact(&mut event)?;
+
+            let fields = &event[event.len() - 8..];
+            let event_type = u16::from_ne_bytes([fields[0], fields[1]]);
+            let code = u16::from_ne_bytes([fields[2], fields[3]]);
+            let value = i32::from_ne_bytes(fields[4..8].try_into().unwrap());
+
+            if event_type == EV_KEY {
+                debug_eprintln!("evdev: key {code}, value {value}");
+                return Ok((code, value != 0));
+            }
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use debug_print::debug_eprintln;
+
+use crate::{
+    calc::{MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P},
+    link::{
+        packet::{pc_machine_id, write_packet, Command, Packet},
+        LinkTransport,
+    },
+    vars::{expect, TransferError, TRANSFER_TIMEOUT},
+};
+
+// grabbing what's on the calculator's LCD over the silent link, same as TiLP's screen capture
+
+// how to draw the screen in a terminal. Braille packs 2x4 pixels into each character, half blocks 1x2, which comes out
+// bigger but works in more fonts
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum RenderStyle {
+    Braille,
+    HalfBlocks,
+}
+
+// the 89 sends the 92+'s whole 240x128 buffer, only the top left of which is on screen
+const TI89_BUFFER_WIDTH: usize = 240;
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct Screenshot {
+    pub width: usize,
+    pub height: usize,
+    // one bit per pixel, MSB first, rows padded out to a whole byte. Set means dark, like the LCD's own memory
+    pub bitmap: Vec<u8>,
+}
+impl Screenshot {
+    pub fn row_len(&self) -> usize {
+        self.width.div_ceil(8)
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.bitmap[y * self.row_len() + x / 8] & (0x80 >> (x % 8)) != 0
+    }
+
+    // anything past the edge counts as unlit, for when the size doesn't divide evenly into characters
+    fn pixel_or_blank(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixel(x, y)
+    }
+
+    // the screen as lines of text, with no trailing newline
+    pub fn render(&self, style: RenderStyle) -> String {
+        let (cell_width, cell_height) = match style {
+            RenderStyle::Braille => (2, 4),
+            RenderStyle::HalfBlocks => (1, 2),
+        };
+
+        let mut lines = Vec::new();
+        for cell_y in (0..self.height).step_by(cell_height) {
+            let line = (0..self.width)
+                .step_by(cell_width)
+                .map(|cell_x| match style {
+                    RenderStyle::Braille => self.braille_cell(cell_x, cell_y),
+                    RenderStyle::HalfBlocks => {
+                        match (
+                            self.pixel_or_blank(cell_x, cell_y),
+                            self.pixel_or_blank(cell_x, cell_y + 1),
+                        ) {
+                            (true, true) => '█',
+                            (true, false) => '▀',
+                            (false, true) => '▄',
+                            (false, false) => ' ',
+                        }
+                    }
+                })
+                .collect::<String>();
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+
+    // braille numbers its dots down the left column then the right, with the bottom row tacked on the end
+    fn braille_cell(&self, x: usize, y: usize) -> char {
+        const DOTS: [(usize, usize, u32); 8] = [
+            (0, 0, 0x01),
+            (0, 1, 0x02),
+            (0, 2, 0x04),
+            (1, 0, 0x08),
+            (1, 1, 0x10),
+            (1, 2, 0x20),
+            (0, 3, 0x40),
+            (1, 3, 0x80),
+        ];
+
+        let bits = DOTS
+            .iter()
+            .filter(|(dx, dy, _)| self.pixel_or_blank(x + dx, y + dy))
+            .fold(0, |bits, (_, _, bit)| bits | bit);
+
+        char::from_u32(0x2800 + bits).unwrap()
+    }
+
+    // binary PBM, which shares the LCD's bit order so the bitmap goes in as is
+    pub fn to_pbm(&self) -> Vec<u8> {
+        let mut bytes = format!("P4\n{} {}\n", self.width, self.height).into_bytes();
+        bytes.extend_from_slice(&self.bitmap);
+        bytes
+    }
+
+    // a 1-bit greyscale PNG. Nothing here is big enough to be worth compressing, so the image data goes in stored
+    // deflate blocks
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&(self.width as u32).to_be_bytes());
+        ihdr.extend_from_slice(&(self.height as u32).to_be_bytes());
+        // bit depth 1, greyscale, then default compression, filtering and no interlacing
+        ihdr.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+        // every row starts with filter type 0. PNG greyscale has 0 as black, so the bits get flipped
+        let mut raw = Vec::new();
+        for row in self.bitmap.chunks(self.row_len()) {
+            raw.push(0);
+            raw.extend(row.iter().map(|byte| !byte));
+        }
+
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        push_png_chunk(&mut bytes, b"IHDR", &ihdr);
+        push_png_chunk(&mut bytes, b"IDAT", &zlib_stored(&raw));
+        push_png_chunk(&mut bytes, b"IEND", &[]);
+        bytes
+    }
+}
+
+// the part of the screen that's actually visible, per model
+pub fn screen_size(machine_id: u8) -> Option<(usize, usize)> {
+    match machine_id {
+        MACHINE_ID_TI89 => Some((160, 100)),
+        MACHINE_ID_TI92P => Some((240, 128)),
+        MACHINE_ID_TI83P => Some((96, 64)),
+        _ => None,
+    }
+}
+
+pub fn screenshot(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+) -> Result<Screenshot, TransferError> {
+    let pc_id = pc_machine_id(machine_id).ok_or(TransferError::UnsupportedModel(machine_id))?;
+    let (width, height) =
+        screen_size(machine_id).ok_or(TransferError::UnsupportedModel(machine_id))?;
+
+    write_packet(link, &Packet::new(pc_id, Command::Scr), TRANSFER_TIMEOUT)?;
+    expect(link, Command::Ack)?;
+    let data = expect(link, Command::Data)?.data;
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+
+    let buffer_width = match machine_id {
+        MACHINE_ID_TI89 => TI89_BUFFER_WIDTH,
+        _ => width,
+    };
+    let buffer_row_len = buffer_width / 8;
+    if data.len() < buffer_row_len * height {
+        debug_eprintln!("screen: only got {} bytes of screen", data.len());
+        return Err(TransferError::UnexpectedPacket(Command::Data));
+    }
+
+    let bitmap = data
+        .chunks(buffer_row_len)
+        .take(height)
+        .flat_map(|row| row[0..width / 8].to_vec())
+        .collect();
+
+    Ok(Screenshot {
+        width,
+        height,
+        bitmap,
+    })
+}
+
+// [length (BE u32), type, data, CRC of the type and data (BE u32)]
+fn push_png_chunk(bytes: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = bytes.len();
+    bytes.extend_from_slice(chunk_type);
+    bytes.extend_from_slice(data);
+    let crc = crc32(&bytes[start..]);
+
+    bytes.extend_from_slice(&crc.to_be_bytes());
+}
+
+// a zlib stream of uncompressed deflate blocks, each at most 65535 bytes
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    while let Some(block) = blocks.next() {
+        // the last block gets the final flag
+        let len = block.len() as u16;
+        bytes.push(blocks.peek().is_none() as u8);
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&(!len).to_le_bytes());
+        bytes.extend_from_slice(block);
+    }
+
+    bytes.extend_from_slice(&adler32(data).to_be_bytes());
+    bytes
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for byte in data {
+        a = (a + *byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
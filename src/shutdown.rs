@@ -0,0 +1,39 @@
+use std::{
+    io, mem, ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Ctrl-C and SIGTERM, turned into a flag that everything which would otherwise wait on the calculator forever keeps an
+// eye on. That way a session gets wound down properly instead of just killed: keys let go, soyuz told to exit, the
+// cable handed back
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::Relaxed)
+}
+
+pub fn request() {
+    REQUESTED.store(true, Ordering::Relaxed);
+}
+
+// the handler puts itself back to the default on the way through, so a second Ctrl-C still kills apollo outright if
+// winding down gets stuck
+pub fn install_handlers() -> io::Result<()> {
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        let mut action = unsafe { mem::zeroed::<libc::sigaction>() };
+        action.sa_sigaction = handle_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESETHAND | libc::SA_RESTART;
+
+        if unsafe { libc::sigaction(signal, &action, ptr::null_mut()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+// nothing but the store, which is all that's safe in a signal handler
+extern "C" fn handle_signal(_signal: libc::c_int) {
+    request();
+}
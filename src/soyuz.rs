@@ -0,0 +1,181 @@
+use std::{fs, io, path::Path};
+
+use debug_print::debug_eprintln;
+
+use crate::{
+    calc::{MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P},
+    formats::{FormatError, TiVar, VarFile},
+    link::LinkTransport,
+    remote::{
+        send_key_no_wait, send_keys, ti83p_char_key, TI83P_KEY_ASM, TI83P_KEY_CLEAR,
+        TI83P_KEY_ENTER, TI83P_KEY_EXEC, TI83P_KEY_QUIT,
+    },
+    vars::{get_var, list_vars, send_var, type_at_home_ti89, DirEntry, TransferError},
+};
+
+// getting soyuz onto the calculator without the user having to fire up TiLP first
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum BundleError {
+    UnsupportedModel(u8),
+    ReadFailed(io::ErrorKind),
+    BadFile(FormatError),
+    // the file parsed fine, it's just for a different calculator
+    WrongModel(u8),
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum LaunchError {
+    NotInstalled,
+    TransferError(TransferError),
+}
+impl From<TransferError> for LaunchError {
+    fn from(e: TransferError) -> Self {
+        LaunchError::TransferError(e)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum InstallOutcome {
+    AlreadyInstalled,
+    Installed,
+    // there was a soyuz, but not the one we've got
+    Updated,
+}
+
+// what soyuz is called on the calculator. The 83+ only does uppercase names
+pub fn var_name(machine_id: u8) -> &'static str {
+    match machine_id {
+        MACHINE_ID_TI83P => "I68SOYUZ",
+        _ => "i68soyuz",
+    }
+}
+
+// what soyuz is called in a release, per model
+pub fn bundle_file_name(machine_id: u8) -> Option<&'static str> {
+    match machine_id {
+        MACHINE_ID_TI92P => Some("i68soyuz.9xz"),
+        MACHINE_ID_TI89 => Some("i68soyuz.89z"),
+        MACHINE_ID_TI83P => Some("i68soyuz.8xp"),
+        _ => None,
+    }
+}
+
+// the soyuz build for `machine_id` out of an unpacked i68soyuz release
+pub fn load_bundle(dir: &Path, machine_id: u8) -> Result<TiVar, BundleError> {
+    let file_name =
+        bundle_file_name(machine_id).ok_or(BundleError::UnsupportedModel(machine_id))?;
+
+    let bytes = match fs::read(dir.join(file_name)) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return Err(BundleError::ReadFailed(e.kind()));
+        }
+    };
+
+    let file = match VarFile::parse(&bytes) {
+        Ok(file) => file,
+        Err(e) => {
+            return Err(BundleError::BadFile(e));
+        }
+    };
+    if file.machine_id != machine_id {
+        return Err(BundleError::WrongModel(file.machine_id));
+    }
+
+    file.into_single().map_err(BundleError::BadFile)
+}
+
+// soyuz doesn't carry its version anywhere we can see without running it, so it counts as current if it's byte for
+// byte the one we'd send. Only fetched when the sizes match, since there's no point otherwise
+pub fn install(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    bundle: &TiVar,
+) -> Result<InstallOutcome, TransferError> {
+    let installed = find_installed(link, machine_id, Some(bundle))?;
+
+    let outcome = match installed {
+        Some(entry) if entry.size as usize == bundle.data.len() => {
+            if get_var(link, machine_id, &entry)?.data == bundle.data {
+                return Ok(InstallOutcome::AlreadyInstalled);
+            }
+            debug_eprintln!("soyuz: installed copy is the same size as ours, but different");
+            InstallOutcome::Updated
+        }
+        Some(entry) => {
+            debug_eprintln!(
+                "soyuz: installed copy is {} bytes, ours is {}",
+                entry.size,
+                bundle.data.len()
+            );
+            InstallOutcome::Updated
+        }
+        None => InstallOutcome::Installed,
+    };
+
+    send_var(link, machine_id, bundle)?;
+
+    Ok(outcome)
+}
+
+// types out the call to soyuz at the home screen and hits ENTER, same as the user would. Once it's up soyuz has the link
+// to itself, so this is the last thing that can go over the silent link. Runs the copy `bundle` was installed as, if
+// there was one
+pub fn launch(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    bundle: Option<&TiVar>,
+) -> Result<(), LaunchError> {
+    let installed = find_installed(link, machine_id, bundle)?.ok_or(LaunchError::NotInstalled)?;
+
+    match machine_id {
+        // QUIT out of whatever menu, CLEAR whatever's typed, then Asm(prgmI68SOYUZ
+        MACHINE_ID_TI83P => {
+            let keycodes = [
+                TI83P_KEY_QUIT,
+                TI83P_KEY_CLEAR,
+                TI83P_KEY_CLEAR,
+                TI83P_KEY_ASM,
+                TI83P_KEY_EXEC,
+            ]
+            .into_iter()
+            .chain(installed.name.chars().filter_map(ti83p_char_key))
+            .collect::<Vec<u16>>();
+
+            send_keys(link, machine_id, &keycodes)?;
+            // the second ACK wouldn't come until soyuz exits, and soyuz's handshake comes first anyway
+            send_key_no_wait(link, machine_id, TI83P_KEY_ENTER)?;
+        }
+        // folder\i68soyuz(), so it doesn't matter what the current folder is
+        _ => {
+            type_at_home_ti89(
+                link,
+                machine_id,
+                &format!("{}\\{}()", installed.folder, installed.name),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// the 89/92+ can have a copy of soyuz in every folder, so it's the one where `bundle` goes that counts, not a stale one
+// left in some other folder. Without a bundle, any copy will do
+fn find_installed(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    bundle: Option<&TiVar>,
+) -> Result<Option<DirEntry>, TransferError> {
+    Ok(list_vars(link, machine_id)?
+        .into_iter()
+        .find(|entry| match bundle {
+            // one without a folder goes in whatever the current folder is, which could be any of them
+            Some(bundle) if !bundle.folder.is_empty() => {
+                entry.folder.eq_ignore_ascii_case(&bundle.folder)
+                    && entry.name.eq_ignore_ascii_case(&bundle.name)
+            }
+            Some(bundle) => entry.name.eq_ignore_ascii_case(&bundle.name),
+            None => entry.name.eq_ignore_ascii_case(var_name(machine_id)),
+        }))
+}
@@ -0,0 +1,258 @@
+use crate::{
+    calc::{MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P},
+    formats::TiVar,
+};
+
+pub mod ti83p;
+pub mod ti89;
+
+// turning TI-BASIC programs into plain text and back, so they can live in git. Anything without a textual form comes
+// out as \x{..} with the byte(s) in hex, and goes back in the same way
+
+const TI89_PRGM: u8 = 0x12;
+const TI89_FUNC: u8 = 0x13;
+const TI83P_PRGM: u8 = 0x05;
+const TI83P_PPRGM: u8 = 0x06;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum TokenError {
+    UnsupportedModel(u8),
+    // (variable type)
+    NotAProgram(u8),
+    Truncated,
+    // the 89/92+ program's been run since it was last edited, so its source is gone
+    Tokenized,
+    // no way of writing this on the calculator
+    UnknownChar(char),
+    TooLong,
+}
+
+pub fn is_program(machine_id: u8, var_type: u8) -> bool {
+    match machine_id {
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => var_type == TI89_PRGM || var_type == TI89_FUNC,
+        MACHINE_ID_TI83P => var_type == TI83P_PRGM || var_type == TI83P_PPRGM,
+        _ => false,
+    }
+}
+
+pub fn program_to_source(machine_id: u8, var: &TiVar) -> Result<String, TokenError> {
+    if !is_program(machine_id, var.var_type) {
+        return Err(TokenError::NotAProgram(var.var_type));
+    }
+
+    // both start with the length, little endian on the 83+ and big endian on the 89/92+
+    let body = var.data.get(2..).ok_or(TokenError::Truncated)?;
+
+    match machine_id {
+        MACHINE_ID_TI83P => Ok(detokenize_ti83p(body)),
+        _ => ti89_source(body),
+    }
+}
+
+// a new program called `name` (in `folder`, on the 89/92+), holding `source`
+pub fn source_to_program(
+    machine_id: u8,
+    folder: &str,
+    name: &str,
+    source: &str,
+) -> Result<TiVar, TokenError> {
+    let (var_type, data) = match machine_id {
+        MACHINE_ID_TI83P => {
+            let tokens = tokenize_ti83p(source)?;
+            let len = u16::try_from(tokens.len()).map_err(|_| TokenError::TooLong)?;
+            (TI83P_PRGM, [&len.to_le_bytes(), tokens.as_slice()].concat())
+        }
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => {
+            let mut body = ti89::SOURCE_START.to_vec();
+            body.extend(encode_ti89(source)?);
+            body.extend_from_slice(ti89::SOURCE_END);
+            body.push(ti89::PRGM_TAG);
+            let len = u16::try_from(body.len()).map_err(|_| TokenError::TooLong)?;
+            (TI89_PRGM, [&len.to_be_bytes(), body.as_slice()].concat())
+        }
+        _ => {
+            return Err(TokenError::UnsupportedModel(machine_id));
+        }
+    };
+
+    Ok(TiVar {
+        folder: match machine_id {
+            MACHINE_ID_TI83P => String::new(),
+            _ => folder.to_string(),
+        },
+        name: name.to_string(),
+        var_type,
+        attr: 0,
+        version: 0,
+        data,
+    })
+}
+
+pub fn detokenize_ti83p(tokens: &[u8]) -> String {
+    // (token, its length, its text)
+    let mut pieces = Vec::new();
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let (token, len) = match tokens.get(i + 1) {
+            Some(second) if ti83p::PREFIXES.contains(&tokens[i]) => {
+                (u16::from_be_bytes([tokens[i], *second]), 2)
+            }
+            _ => (tokens[i] as u16, 1),
+        };
+
+        let text = ti83p::TOKENS
+            .iter()
+            .find(|(id, _)| *id == token)
+            .map(|(_, text)| *text);
+        pieces.push((token, len, text));
+        i += len;
+    }
+
+    // a token only keeps its text if tokenizing reads that text back as the same token, given what comes after it.
+    // Otherwise it's escaped, e.g. the space in space, o, r, space, which would come back as the one token " or ", or
+    // a backslash that'd read back as the start of an escape. Done back to front, so what comes after is already known
+    let longest = ti83p::TOKENS
+        .iter()
+        .map(|(_, text)| text.len())
+        .max()
+        .unwrap_or(0);
+    let mut texts: Vec<String> = Vec::new();
+    for (token, len, text) in pieces.iter().rev() {
+        let mut following = String::new();
+        for next in texts.iter().rev() {
+            if following.len() >= longest {
+                break;
+            }
+            following.push_str(next);
+        }
+
+        let bytes = if *len == 2 {
+            token.to_be_bytes().to_vec()
+        } else {
+            vec![*token as u8]
+        };
+        texts.push(match text {
+            Some(text)
+                if read_token_ti83p(&format!("{text}{following}")) == Ok((bytes, text.len())) =>
+            {
+                text.to_string()
+            }
+            _ if *len == 2 => format!("\\x{{{token:04X}}}"),
+            _ => format!("\\x{{{token:02X}}}"),
+        });
+    }
+
+    texts.into_iter().rev().collect()
+}
+
+// greedy, the longest token that matches wins, so "sin(" is the one token rather than s, i, n and (
+pub fn tokenize_ti83p(source: &str) -> Result<Vec<u8>, TokenError> {
+    let mut tokens = Vec::new();
+
+    let mut rest = source;
+    while !rest.is_empty() {
+        let (bytes, len) = read_token_ti83p(rest)?;
+        tokens.extend(bytes);
+        rest = &rest[len..];
+    }
+
+    Ok(tokens)
+}
+
+// the token (or escape) `source` starts with, and how much of `source` it takes up
+fn read_token_ti83p(source: &str) -> Result<(Vec<u8>, usize), TokenError> {
+    if let Some(escape) = parse_escape(source) {
+        return Ok(escape);
+    }
+
+    let (token, text) = ti83p::TOKENS
+        .iter()
+        .filter(|(_, text)| source.starts_with(text))
+        .max_by_key(|(_, text)| text.len())
+        .ok_or(TokenError::UnknownChar(
+            source.chars().next().unwrap_or('\0'),
+        ))?;
+
+    let bytes = if *token > 0xFF {
+        token.to_be_bytes().to_vec()
+    } else {
+        vec![*token as u8]
+    };
+    Ok((bytes, text.len()))
+}
+
+fn ti89_source(body: &[u8]) -> Result<String, TokenError> {
+    let text = body
+        .strip_prefix(ti89::SOURCE_START)
+        .ok_or(TokenError::Tokenized)?;
+    let text_len = text
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or(TokenError::Truncated)?;
+
+    Ok(decode_ti89(&text[..text_len]))
+}
+
+// lines end in a carriage return on the calculator. A backslash followed by "x{", e.g. in a string, would read back as
+// an escape, so that backslash gets escaped
+fn decode_ti89(text: &[u8]) -> String {
+    text.iter()
+        .enumerate()
+        .map(
+            |(i, byte)| match ti89::CHARSET.iter().find(|(id, _)| id == byte) {
+                Some((_, c)) => c.to_string(),
+                None => match byte {
+                    b'\r' => "\n".to_string(),
+                    b'\\' if text[i + 1..].starts_with(b"x{") => format!("\\x{{{byte:02X}}}"),
+                    b' '..=b'~' | 0xA1..=0xFF => (*byte as char).to_string(),
+                    _ => format!("\\x{{{byte:02X}}}"),
+                },
+            },
+        )
+        .collect()
+}
+
+fn encode_ti89(source: &str) -> Result<Vec<u8>, TokenError> {
+    let mut text = Vec::new();
+
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if let Some((bytes, escape_len)) = parse_escape(rest) {
+            text.extend(bytes);
+            rest = &rest[escape_len..];
+            continue;
+        }
+
+        let byte = match ti89::CHARSET.iter().find(|(_, charset_c)| *charset_c == c) {
+            Some((id, _)) => *id,
+            None => match c {
+                '\n' => b'\r',
+                ' '..='~' | '\u{A1}'..='\u{FF}' => c as u8,
+                _ => {
+                    return Err(TokenError::UnknownChar(c));
+                }
+            },
+        };
+        text.push(byte);
+        rest = &rest[c.len_utf8()..];
+    }
+
+    Ok(text)
+}
+
+// \x{HH} or \x{HHHH}, giving the byte(s) and how long the escape was
+fn parse_escape(text: &str) -> Option<(Vec<u8>, usize)> {
+    let hex = text.strip_prefix("\\x{")?;
+    let hex = &hex[..hex.find('}')?];
+
+    let bytes = match hex.len() {
+        2 => vec![u8::from_str_radix(hex, 16).ok()?],
+        4 => u16::from_str_radix(hex, 16).ok()?.to_be_bytes().to_vec(),
+        _ => {
+            return None;
+        }
+    };
+
+    Some((bytes, "\\x{}".len() + hex.len()))
+}
@@ -0,0 +1,374 @@
+// TI-83 Plus tokens and the text they stand for, as (token, text). Two byte tokens have their prefix byte in the high
+// byte. Only covers the tokens a program's likely to use, anything else comes out escaped
+
+// the first byte of every two byte token
+pub const PREFIXES: &[u8] = &[
+    0x5C, 0x5D, 0x5E, 0x60, 0x61, 0x62, 0x63, 0x7E, 0xAA, 0xBB, 0xEF,
+];
+
+pub const TOKENS: &[(u16, &str)] = &[
+    (0x01, "►DMS"),
+    (0x02, "►Dec"),
+    (0x03, "►Frac"),
+    (0x04, "→"),
+    (0x05, "Boxplot"),
+    (0x06, "["),
+    (0x07, "]"),
+    (0x08, "{"),
+    (0x09, "}"),
+    (0x0A, "ʳ"),
+    (0x0B, "°"),
+    (0x0C, "⁻¹"),
+    (0x0D, "²"),
+    (0x0E, "ᵀ"),
+    (0x0F, "³"),
+    (0x10, "("),
+    (0x11, ")"),
+    (0x12, "round("),
+    (0x13, "pxl-Test("),
+    (0x14, "augment("),
+    (0x15, "rowSwap("),
+    (0x16, "row+("),
+    (0x17, "*row("),
+    (0x18, "*row+("),
+    (0x19, "max("),
+    (0x1A, "min("),
+    (0x1B, "R►Pr("),
+    (0x1C, "R►Pθ("),
+    (0x1D, "P►Rx("),
+    (0x1E, "P►Ry("),
+    (0x1F, "median("),
+    (0x20, "randM("),
+    (0x21, "mean("),
+    (0x22, "solve("),
+    (0x23, "seq("),
+    (0x24, "fnInt("),
+    (0x25, "nDeriv("),
+    (0x27, "fMin("),
+    (0x28, "fMax("),
+    (0x29, " "),
+    (0x2A, "\""),
+    (0x2B, ","),
+    (0x2C, "ⅈ"),
+    (0x2D, "!"),
+    (0x2E, "CubicReg "),
+    (0x2F, "QuartReg "),
+    (0x30, "0"),
+    (0x31, "1"),
+    (0x32, "2"),
+    (0x33, "3"),
+    (0x34, "4"),
+    (0x35, "5"),
+    (0x36, "6"),
+    (0x37, "7"),
+    (0x38, "8"),
+    (0x39, "9"),
+    (0x3A, "."),
+    (0x3B, "ᴇ"),
+    (0x3C, " or "),
+    (0x3D, " xor "),
+    (0x3E, ":"),
+    (0x3F, "\n"),
+    (0x40, " and "),
+    (0x41, "A"),
+    (0x42, "B"),
+    (0x43, "C"),
+    (0x44, "D"),
+    (0x45, "E"),
+    (0x46, "F"),
+    (0x47, "G"),
+    (0x48, "H"),
+    (0x49, "I"),
+    (0x4A, "J"),
+    (0x4B, "K"),
+    (0x4C, "L"),
+    (0x4D, "M"),
+    (0x4E, "N"),
+    (0x4F, "O"),
+    (0x50, "P"),
+    (0x51, "Q"),
+    (0x52, "R"),
+    (0x53, "S"),
+    (0x54, "T"),
+    (0x55, "U"),
+    (0x56, "V"),
+    (0x57, "W"),
+    (0x58, "X"),
+    (0x59, "Y"),
+    (0x5A, "Z"),
+    (0x5B, "θ"),
+    (0x5F, "prgm"),
+    (0x64, "Radian"),
+    (0x65, "Degree"),
+    (0x66, "Normal"),
+    (0x67, "Sci"),
+    (0x68, "Eng"),
+    (0x69, "Float"),
+    (0x6A, "="),
+    (0x6B, "<"),
+    (0x6C, ">"),
+    (0x6D, "≤"),
+    (0x6E, "≥"),
+    (0x6F, "≠"),
+    (0x70, "+"),
+    (0x71, "-"),
+    (0x72, "Ans"),
+    (0x73, "Fix "),
+    (0x74, "Horiz"),
+    (0x75, "Full"),
+    (0x76, "Func"),
+    (0x77, "Param"),
+    (0x78, "Polar"),
+    (0x79, "Seq"),
+    (0x7A, "IndpntAuto"),
+    (0x7B, "IndpntAsk"),
+    (0x7C, "DependAuto"),
+    (0x7D, "DependAsk"),
+    (0x82, "*"),
+    (0x83, "/"),
+    (0x84, "Trace"),
+    (0x85, "ClrDraw"),
+    (0x86, "ZStandard"),
+    (0x87, "ZTrig"),
+    (0x88, "ZBox"),
+    (0x89, "Zoom In"),
+    (0x8A, "Zoom Out"),
+    (0x8B, "ZSquare"),
+    (0x8C, "ZInteger"),
+    (0x8D, "ZPrevious"),
+    (0x8E, "ZDecimal"),
+    (0x8F, "ZoomStat"),
+    (0x90, "ZoomRcl"),
+    (0x91, "PrintScreen"),
+    (0x92, "ZoomSto"),
+    (0x93, "Text("),
+    (0x94, " nPr "),
+    (0x95, " nCr "),
+    (0x96, "FnOn "),
+    (0x97, "FnOff "),
+    (0x98, "StorePic "),
+    (0x99, "RecallPic "),
+    (0x9A, "StoreGDB "),
+    (0x9B, "RecallGDB "),
+    (0x9C, "Line("),
+    (0x9D, "Vertical "),
+    (0x9E, "Pt-On("),
+    (0x9F, "Pt-Off("),
+    (0xA0, "Pt-Change("),
+    (0xA1, "Pxl-On("),
+    (0xA2, "Pxl-Off("),
+    (0xA3, "Pxl-Change("),
+    (0xA4, "Shade("),
+    (0xA5, "Circle("),
+    (0xA6, "Horizontal "),
+    (0xA7, "Tangent("),
+    (0xA8, "DrawInv "),
+    (0xA9, "DrawF "),
+    (0xAB, "rand"),
+    (0xAC, "π"),
+    (0xAD, "getKey"),
+    (0xAE, "'"),
+    (0xAF, "?"),
+    (0xB0, "⁻"),
+    (0xB1, "int("),
+    (0xB2, "abs("),
+    (0xB3, "det("),
+    (0xB4, "identity("),
+    (0xB5, "dim("),
+    (0xB6, "sum("),
+    (0xB7, "prod("),
+    (0xB8, "not("),
+    (0xB9, "iPart("),
+    (0xBA, "fPart("),
+    (0xBC, "√("),
+    (0xBD, "³√("),
+    (0xBE, "ln("),
+    (0xBF, "e^("),
+    (0xC0, "log("),
+    (0xC1, "₁₀^("),
+    (0xC2, "sin("),
+    (0xC3, "sin⁻¹("),
+    (0xC4, "cos("),
+    (0xC5, "cos⁻¹("),
+    (0xC6, "tan("),
+    (0xC7, "tan⁻¹("),
+    (0xC8, "sinh("),
+    (0xC9, "sinh⁻¹("),
+    (0xCA, "cosh("),
+    (0xCB, "cosh⁻¹("),
+    (0xCC, "tanh("),
+    (0xCD, "tanh⁻¹("),
+    (0xCE, "If "),
+    (0xCF, "Then"),
+    (0xD0, "Else"),
+    (0xD1, "While "),
+    (0xD2, "Repeat "),
+    (0xD3, "For("),
+    (0xD4, "End"),
+    (0xD5, "Return"),
+    (0xD6, "Lbl "),
+    (0xD7, "Goto "),
+    (0xD8, "Pause "),
+    (0xD9, "Stop"),
+    (0xDA, "IS>("),
+    (0xDB, "DS<("),
+    (0xDC, "Input "),
+    (0xDD, "Prompt "),
+    (0xDE, "Disp "),
+    (0xDF, "DispGraph"),
+    (0xE0, "Output("),
+    (0xE1, "ClrHome"),
+    (0xE2, "Fill("),
+    (0xE3, "SortA("),
+    (0xE4, "SortD("),
+    (0xE5, "DispTable"),
+    (0xE6, "Menu("),
+    (0xE7, "Send("),
+    (0xE8, "Get("),
+    (0xE9, "PlotsOn "),
+    (0xEA, "PlotsOff "),
+    (0xEB, "⌊"),
+    (0xEC, "Plot1("),
+    (0xED, "Plot2("),
+    (0xEE, "Plot3("),
+    (0xF0, "^"),
+    (0xF1, "×√"),
+    (0xF2, "1-Var Stats "),
+    (0xF3, "2-Var Stats "),
+    (0xF4, "LinReg(a+bx) "),
+    (0xF5, "ExpReg "),
+    (0xF6, "LnReg "),
+    (0xF7, "PwrReg "),
+    (0xF8, "Med-Med "),
+    (0xF9, "QuadReg "),
+    (0xFA, "ClrList "),
+    (0xFB, "ClrTable"),
+    (0xFC, "Histogram"),
+    (0xFD, "xyLine"),
+    (0xFE, "Scatter"),
+    (0xFF, "LinReg(ax+b) "),
+    (0x5C00, "[A]"),
+    (0x5C01, "[B]"),
+    (0x5C02, "[C]"),
+    (0x5C03, "[D]"),
+    (0x5C04, "[E]"),
+    (0x5C05, "[F]"),
+    (0x5C06, "[G]"),
+    (0x5C07, "[H]"),
+    (0x5C08, "[I]"),
+    (0x5C09, "[J]"),
+    (0x5D00, "L₁"),
+    (0x5D01, "L₂"),
+    (0x5D02, "L₃"),
+    (0x5D03, "L₄"),
+    (0x5D04, "L₅"),
+    (0x5D05, "L₆"),
+    (0x5E10, "Y₁"),
+    (0x5E11, "Y₂"),
+    (0x5E12, "Y₃"),
+    (0x5E13, "Y₄"),
+    (0x5E14, "Y₅"),
+    (0x5E15, "Y₆"),
+    (0x5E16, "Y₇"),
+    (0x5E17, "Y₈"),
+    (0x5E18, "Y₉"),
+    (0x5E19, "Y₀"),
+    // the sequence variables, not the lowercase letters further down
+    (0x5E80, "|u"),
+    (0x5E81, "|v"),
+    (0x5E82, "|w"),
+    (0x6000, "Pic1"),
+    (0x6001, "Pic2"),
+    (0x6002, "Pic3"),
+    (0x6003, "Pic4"),
+    (0x6004, "Pic5"),
+    (0x6005, "Pic6"),
+    (0x6006, "Pic7"),
+    (0x6007, "Pic8"),
+    (0x6008, "Pic9"),
+    (0x6009, "Pic0"),
+    (0x6100, "GDB1"),
+    (0x6101, "GDB2"),
+    (0x6102, "GDB3"),
+    (0x6103, "GDB4"),
+    (0x6104, "GDB5"),
+    (0x6105, "GDB6"),
+    (0x6106, "GDB7"),
+    (0x6107, "GDB8"),
+    (0x6108, "GDB9"),
+    (0x6109, "GDB0"),
+    (0x6302, "Xscl"),
+    (0x6303, "Yscl"),
+    (0x630A, "Xmin"),
+    (0x630B, "Xmax"),
+    (0x630C, "Ymin"),
+    (0x630D, "Ymax"),
+    (0xAA00, "Str1"),
+    (0xAA01, "Str2"),
+    (0xAA02, "Str3"),
+    (0xAA03, "Str4"),
+    (0xAA04, "Str5"),
+    (0xAA05, "Str6"),
+    (0xAA06, "Str7"),
+    (0xAA07, "Str8"),
+    (0xAA08, "Str9"),
+    (0xAA09, "Str0"),
+    (0xBB00, "npv("),
+    (0xBB01, "irr("),
+    (0xBB02, "bal("),
+    (0xBB03, "ΣPrn("),
+    (0xBB04, "ΣInt("),
+    (0xBB05, "►Nom("),
+    (0xBB06, "►Eff("),
+    (0xBB07, "dbd("),
+    (0xBB08, "lcm("),
+    (0xBB09, "gcd("),
+    (0xBB0A, "randInt("),
+    (0xBB0B, "randBin("),
+    (0xBB0C, "sub("),
+    (0xBB0D, "stdDev("),
+    (0xBB0E, "variance("),
+    (0xBB0F, "inString("),
+    (0xBB10, "normalcdf("),
+    (0xBB11, "invNorm("),
+    (0xBB12, "tcdf("),
+    (0xBB13, "χ²cdf("),
+    (0xBB14, "Fcdf("),
+    (0xBB15, "binompdf("),
+    (0xBB16, "binomcdf("),
+    (0xBB17, "poissonpdf("),
+    (0xBB18, "poissoncdf("),
+    (0xBB19, "geometpdf("),
+    (0xBB1A, "geometcdf("),
+    (0xBB1B, "normalpdf("),
+    (0xBB1C, "tpdf("),
+    (0xBB1D, "χ²pdf("),
+    (0xBB1E, "Fpdf("),
+    (0xBB1F, "randNorm("),
+    (0xBBB0, "a"),
+    (0xBBB1, "b"),
+    (0xBBB2, "c"),
+    (0xBBB3, "d"),
+    (0xBBB4, "e"),
+    (0xBBB5, "f"),
+    (0xBBB6, "g"),
+    (0xBBB7, "h"),
+    (0xBBB8, "i"),
+    (0xBBB9, "j"),
+    (0xBBBA, "k"),
+    (0xBBBC, "l"),
+    (0xBBBD, "m"),
+    (0xBBBE, "n"),
+    (0xBBBF, "o"),
+    (0xBBC0, "p"),
+    (0xBBC1, "q"),
+    (0xBBC2, "r"),
+    (0xBBC3, "s"),
+    (0xBBC4, "t"),
+    (0xBBC5, "u"),
+    (0xBBC6, "v"),
+    (0xBBC7, "w"),
+    (0xBBC8, "x"),
+    (0xBBC9, "y"),
+    (0xBBCA, "z"),
+];
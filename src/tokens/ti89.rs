@@ -0,0 +1,47 @@
+// the 89/92+ character set where it differs from Latin-1, as (byte, char)
+pub const CHARSET: &[(u8, char)] = &[
+    (0x16, '→'),
+    (0x80, 'α'),
+    (0x81, 'β'),
+    (0x82, 'Γ'),
+    (0x83, 'γ'),
+    (0x84, 'Δ'),
+    (0x85, 'δ'),
+    (0x86, 'ε'),
+    (0x87, 'ζ'),
+    (0x88, 'θ'),
+    (0x89, 'λ'),
+    (0x8A, 'ξ'),
+    (0x8B, 'Π'),
+    (0x8C, 'π'),
+    (0x8D, 'ρ'),
+    (0x8E, 'Σ'),
+    (0x8F, 'σ'),
+    (0x90, 'τ'),
+    (0x91, 'φ'),
+    (0x92, 'ψ'),
+    (0x93, 'Ω'),
+    (0x94, 'ω'),
+    (0x95, 'ᴇ'),
+    (0x96, 'ℯ'),
+    (0x97, 'ⅈ'),
+    (0x98, 'ʳ'),
+    (0x99, 'ᵀ'),
+    (0x9C, '≤'),
+    (0x9D, '≠'),
+    (0x9E, '≥'),
+    (0x9F, '∠'),
+    (0xA0, '…'),
+    (0xAD, '⁻'),
+];
+
+// a program that's been edited but not run since is kept as its source text, which is the only form we deal in. Running
+// it swaps that out for the tokenized form
+//
+// [0, 0, 0, source, 0, the empty argument list and the rest of the program's structure, tag]
+pub const SOURCE_START: &[u8] = &[0x00, 0x00, 0x00];
+pub const SOURCE_END: &[u8] = &[
+    0x00, 0xE9, 0x12, 0xE4, 0x00, 0xE8, 0x19, 0xE4, 0xE5, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08,
+];
+pub const PRGM_TAG: u8 = 0xDC;
+pub const FUNC_TAG: u8 = 0xDD;
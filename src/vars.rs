@@ -0,0 +1,551 @@
+use std::time::Duration;
+
+use debug_print::debug_eprintln;
+
+use crate::{
+    calc::{MACHINE_ID_TI83P, MACHINE_ID_TI89, MACHINE_ID_TI92P},
+    formats::{is_archived, padded_string, TiVar, TI89_ATTR_ARCHIVED, TI89_ATTR_LOCKED},
+    link::{
+        packet::{
+            pc_machine_id, read_packet, write_packet, Command, Packet, PacketError, TI83P_TO_PC,
+            TI89_TO_PC, TI92P_TO_PC,
+        },
+        LinkIoError, LinkTransport,
+    },
+    remote::{send_keys, TI89_KEY_CLEAR, TI89_KEY_ENTER, TI89_KEY_HOME},
+};
+
+// "silent" transfers, i.e. the ones the calculator's OS answers on its own from the home screen, no Send/Receive menu
+// needed. Which also means none of this works while soyuz (or anything else) is running
+
+// how long to wait on the calculator during a transfer. Generous, since it might have to garbage collect first
+pub const TRANSFER_TIMEOUT: Duration = Duration::from_secs(5);
+
+// variable types with a special meaning to the directory listing
+pub const TI89_DIR: u8 = 0x1F;
+// FlashApps show up alongside the folders on the 89/92+, with this type
+pub const FLASH_APP: u8 = 0x24;
+const TI89_RDIR: u8 = 0x1A;
+const TI89_LDIR: u8 = 0x1B;
+const TI83P_DIR: u8 = 0x19;
+// the 89 Titanium's clock, which goes back and forth like a variable called Clock
+const TI89_CLK: u8 = 0x18;
+
+// 1997-01-01 00:00:00, which the calculator's clock counts up from. In local time, as far as the calculator's concerned
+pub const CLOCK_EPOCH: i64 = 852_076_800;
+
+// size of each entry in an 89/92+ directory listing
+const TI89_DIR_ENTRY_LEN: usize = 14;
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub enum TransferError {
+    PacketError(PacketError),
+    // the calc answered with something other than what comes next in the protocol
+    UnexpectedPacket(Command),
+    // it said no, usually for lack of memory or because the variable is locked or archived
+    Refused,
+    NotFound,
+    UnsupportedModel(u8),
+}
+impl From<PacketError> for TransferError {
+    fn from(e: PacketError) -> Self {
+        TransferError::PacketError(e)
+    }
+}
+impl From<LinkIoError> for TransferError {
+    fn from(e: LinkIoError) -> Self {
+        TransferError::PacketError(PacketError::LinkError(e))
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub struct DirEntry {
+    // always empty on the 83+
+    pub folder: String,
+    pub name: String,
+    pub var_type: u8,
+    pub attr: u8,
+    // in the same terms as `TiVar::data`'s length
+    pub size: u32,
+}
+
+// finds out what's on the other end of the link by asking if it's ready and seeing who answers
+pub fn probe_model(link: &mut dyn LinkTransport) -> Result<u8, TransferError> {
+    let mut last_error = TransferError::UnsupportedModel(0);
+
+    for pc_id in [MACHINE_ID_TI89, MACHINE_ID_TI83P].map(|id| pc_machine_id(id).unwrap()) {
+        write_packet(link, &Packet::new(pc_id, Command::Rdy), TRANSFER_TIMEOUT)?;
+
+        let ack = match expect(link, Command::Ack) {
+            Ok(ack) => ack,
+            Err(e) => {
+                debug_eprintln!("vars: no answer to {pc_id:#04x}. Reason: {e:?}");
+                last_error = e;
+                continue;
+            }
+        };
+
+        return match ack.machine_id {
+            TI89_TO_PC => Ok(MACHINE_ID_TI89),
+            TI92P_TO_PC => Ok(MACHINE_ID_TI92P),
+            TI83P_TO_PC => Ok(MACHINE_ID_TI83P),
+            machine_id => Err(TransferError::UnsupportedModel(machine_id)),
+        };
+    }
+
+    Err(last_error)
+}
+
+pub fn list_vars(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+) -> Result<Vec<DirEntry>, TransferError> {
+    match machine_id {
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => list_vars_ti89(link),
+        MACHINE_ID_TI83P => list_vars_ti83p(link),
+        _ => Err(TransferError::UnsupportedModel(machine_id)),
+    }
+}
+
+pub fn send_var(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    var: &TiVar,
+) -> Result<(), TransferError> {
+    let pc_id = pc_machine_id(machine_id).ok_or(TransferError::UnsupportedModel(machine_id))?;
+
+    let (header, data) = match machine_id {
+        MACHINE_ID_TI83P => (
+            var_header_ti83p(var.data.len() as u16, var.var_type, &var.name)
+                .into_iter()
+                .chain([var.version, var.attr])
+                .collect(),
+            var.data.clone(),
+        ),
+        // and the 89/92+ want four bytes of something in front of the data. They're always zero
+        _ => (
+            var_header_ti89(
+                var.data.len() as u32,
+                var.var_type,
+                &full_name(&var.folder, &var.name),
+            ),
+            [0; 4].into_iter().chain(var.data.iter().copied()).collect(),
+        ),
+    };
+
+    debug_eprintln!("vars: sending {}", full_name(&var.folder, &var.name));
+
+    write_packet(
+        link,
+        &Packet::with_data(pc_id, Command::Rts, header),
+        TRANSFER_TIMEOUT,
+    )?;
+    expect(link, Command::Ack)?;
+    expect(link, Command::Cts)?;
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+    write_packet(
+        link,
+        &Packet::with_data(pc_id, Command::Data, data),
+        TRANSFER_TIMEOUT,
+    )?;
+    expect(link, Command::Ack)?;
+    write_packet(link, &Packet::new(pc_id, Command::Eot), TRANSFER_TIMEOUT)?;
+    expect(link, Command::Ack)?;
+
+    // the 83+ takes the archive flag as part of the header, the 89/92+ have to be told separately
+    if machine_id != MACHINE_ID_TI83P && is_archived(machine_id, var.attr) {
+        type_at_home_ti89(
+            link,
+            machine_id,
+            &format!("archive {}", full_name(&var.folder, &var.name)),
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn get_var(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    entry: &DirEntry,
+) -> Result<TiVar, TransferError> {
+    let (version, data) = match machine_id {
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => (
+            0,
+            request_ti89(link, entry.var_type, &full_name(&entry.folder, &entry.name))?.1,
+        ),
+        MACHINE_ID_TI83P => {
+            let pc_id = pc_machine_id(MACHINE_ID_TI83P).unwrap();
+
+            let mut request = var_header_ti83p(entry.size as u16, entry.var_type, &entry.name);
+            request.extend_from_slice(&[0x00, entry.attr]);
+            write_packet(
+                link,
+                &Packet::with_data(pc_id, Command::Req, request),
+                TRANSFER_TIMEOUT,
+            )?;
+            // a nonzero status means it hasn't got it
+            if expect(link, Command::Ack)?.param != 0 {
+                return Err(TransferError::NotFound);
+            }
+            // [size, type, name, version, flag]. Pre-flash OSes leave the last two off
+            let header = expect(link, Command::Var)?.data;
+            write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+            write_packet(link, &Packet::new(pc_id, Command::Cts), TRANSFER_TIMEOUT)?;
+            expect(link, Command::Ack)?;
+            let data = expect(link, Command::Data)?.data;
+            write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+
+            (header.get(11).copied().unwrap_or(0), data)
+        }
+        _ => {
+            return Err(TransferError::UnsupportedModel(machine_id));
+        }
+    };
+
+    Ok(TiVar {
+        folder: entry.folder.clone(),
+        name: entry.name.clone(),
+        var_type: entry.var_type,
+        attr: entry.attr,
+        version,
+        data,
+    })
+}
+
+pub fn delete_var(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    entry: &DirEntry,
+) -> Result<(), TransferError> {
+    match machine_id {
+        // the 89/92+ have no delete packet, so it's done the way TiLP does it: typing DelVar at the home screen
+        MACHINE_ID_TI89 | MACHINE_ID_TI92P => {
+            let name = full_name(&entry.folder, &entry.name);
+            let command = if is_archived(machine_id, entry.attr) {
+                format!("unarchiv {name}:delvar {name}")
+            } else {
+                format!("delvar {name}")
+            };
+            type_at_home_ti89(link, machine_id, &command)
+        }
+        MACHINE_ID_TI83P => {
+            let pc_id = pc_machine_id(MACHINE_ID_TI83P).unwrap();
+
+            let mut request = var_header_ti83p(entry.size as u16, entry.var_type, &entry.name);
+            request.extend_from_slice(&[0x00, entry.attr]);
+            write_packet(
+                link,
+                &Packet::with_data(pc_id, Command::Del, request),
+                TRANSFER_TIMEOUT,
+            )?;
+            // once for the request, once for it being done
+            expect(link, Command::Ack)?;
+            expect(link, Command::Ack)?;
+
+            Ok(())
+        }
+        _ => Err(TransferError::UnsupportedModel(machine_id)),
+    }
+}
+
+// the OS version as (major, minor)
+pub fn get_version(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+) -> Result<(u8, u8), TransferError> {
+    let pc_id = pc_machine_id(machine_id).ok_or(TransferError::UnsupportedModel(machine_id))?;
+
+    write_packet(link, &Packet::new(pc_id, Command::Ver), TRANSFER_TIMEOUT)?;
+    expect(link, Command::Ack)?;
+    write_packet(link, &Packet::new(pc_id, Command::Cts), TRANSFER_TIMEOUT)?;
+    expect(link, Command::Ack)?;
+    // [OS major, OS minor, boot major, boot minor, ...]
+    let data = expect(link, Command::Data)?.data;
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+
+    match data.as_slice() {
+        [major, minor, ..] => Ok((*major, *minor)),
+        _ => Err(TransferError::UnexpectedPacket(Command::Data)),
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Copy, Clone)]
+pub struct CalcClock {
+    // since `CLOCK_EPOCH`
+    pub seconds: u32,
+    // how the calculator shows the date and time. Sent back as they came, so setting the time doesn't change them
+    pub date_format: u8,
+    pub time_format: u8,
+}
+
+// only the 89 Titanium has a clock, and it answers as an 89. A plain 89 refuses. The 84 Plus has one too, but it's
+// only reachable over its USB port
+pub fn get_clock(link: &mut dyn LinkTransport, machine_id: u8) -> Result<CalcClock, TransferError> {
+    if machine_id != MACHINE_ID_TI89 {
+        return Err(TransferError::UnsupportedModel(machine_id));
+    }
+
+    // [0, 0, seconds (BE u32), date format, time format, ...]
+    match request_ti89(link, TI89_CLK, "Clock")?.1.as_slice() {
+        [_, _, s0, s1, s2, s3, date_format, time_format, ..] => Ok(CalcClock {
+            seconds: u32::from_be_bytes([*s0, *s1, *s2, *s3]),
+            date_format: *date_format,
+            time_format: *time_format,
+        }),
+        _ => Err(TransferError::UnexpectedPacket(Command::Data)),
+    }
+}
+
+pub fn set_clock(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    clock: &CalcClock,
+) -> Result<(), TransferError> {
+    if machine_id != MACHINE_ID_TI89 {
+        return Err(TransferError::UnsupportedModel(machine_id));
+    }
+    let pc_id = pc_machine_id(machine_id).unwrap();
+
+    // the usual four zero bytes, then the same layout it comes back in, with 0xFF after the formats
+    let mut data = vec![0; 16];
+    data[6..10].copy_from_slice(&clock.seconds.to_be_bytes());
+    data[10] = clock.date_format;
+    data[11] = clock.time_format;
+    data[12] = 0xFF;
+
+    write_packet(
+        link,
+        &Packet::with_data(
+            pc_id,
+            Command::Rts,
+            var_header_ti89(data.len() as u32, TI89_CLK, "Clock"),
+        ),
+        TRANSFER_TIMEOUT,
+    )?;
+    expect(link, Command::Ack)?;
+    expect(link, Command::Cts)?;
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+    write_packet(
+        link,
+        &Packet::with_data(pc_id, Command::Data, data),
+        TRANSFER_TIMEOUT,
+    )?;
+    expect(link, Command::Ack)?;
+    write_packet(link, &Packet::new(pc_id, Command::Eot), TRANSFER_TIMEOUT)?;
+    expect(link, Command::Ack)?;
+
+    Ok(())
+}
+
+// looks a variable up by name, or folder\name on the 89/92+
+pub fn find_var(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    name: &str,
+) -> Result<DirEntry, TransferError> {
+    let (folder, name) = match name.split_once('\\') {
+        Some((folder, name)) => (Some(folder), name),
+        None => (None, name),
+    };
+
+    list_vars(link, machine_id)?
+        .into_iter()
+        .find(|entry| {
+            entry.name.eq_ignore_ascii_case(name)
+                && folder.is_none_or(|folder| entry.folder.eq_ignore_ascii_case(folder))
+        })
+        .ok_or(TransferError::NotFound)
+}
+
+// HOME, CLEAR the entry line, type it out, ENTER
+pub(crate) fn type_at_home_ti89(
+    link: &mut dyn LinkTransport,
+    machine_id: u8,
+    text: &str,
+) -> Result<(), TransferError> {
+    let keycodes = [TI89_KEY_HOME, TI89_KEY_CLEAR, TI89_KEY_CLEAR]
+        .into_iter()
+        .chain(text.chars().map(|c| c as u16))
+        .chain([TI89_KEY_ENTER])
+        .collect::<Vec<u16>>();
+
+    send_keys(link, machine_id, &keycodes)
+}
+
+// the 89/92+ list the folders first, then each folder's contents has to be asked for separately
+fn list_vars_ti89(link: &mut dyn LinkTransport) -> Result<Vec<DirEntry>, TransferError> {
+    let mut entries = Vec::new();
+
+    let folders = read_dir_ti89(link, TI89_RDIR, "")?;
+    entries.extend(
+        folders
+            .iter()
+            .filter(|entry| entry.var_type == FLASH_APP)
+            .cloned(),
+    );
+    for folder in folders.iter().filter(|entry| entry.var_type == TI89_DIR) {
+        // the first entry is the folder itself
+        for mut entry in read_dir_ti89(link, TI89_LDIR, &folder.name)?
+            .into_iter()
+            .skip(1)
+        {
+            entry.folder = folder.name.clone();
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+fn read_dir_ti89(
+    link: &mut dyn LinkTransport,
+    dir_type: u8,
+    folder: &str,
+) -> Result<Vec<DirEntry>, TransferError> {
+    let (_, listing) = request_ti89(link, dir_type, folder)?;
+
+    Ok(listing
+        .chunks_exact(TI89_DIR_ENTRY_LEN)
+        .map(|entry| DirEntry {
+            folder: String::new(),
+            name: padded_string(&entry[0..8]),
+            var_type: entry[8],
+            // listings flag archived and locked with a bit each, so turn that into the attribute a file would have
+            attr: if entry[9] & 0x02 != 0 {
+                TI89_ATTR_ARCHIVED
+            } else if entry[9] & 0x01 != 0 {
+                TI89_ATTR_LOCKED
+            } else {
+                0
+            },
+            size: u32::from_le_bytes([entry[10], entry[11], entry[12], 0]),
+        })
+        .collect())
+}
+
+// asks for a variable (or a directory listing, which the 89/92+ treat the same way) and returns the VAR header that
+// comes back along with the data
+fn request_ti89(
+    link: &mut dyn LinkTransport,
+    var_type: u8,
+    name: &str,
+) -> Result<(Vec<u8>, Vec<u8>), TransferError> {
+    let pc_id = pc_machine_id(MACHINE_ID_TI89).unwrap();
+
+    let request = var_header_ti89(0, var_type, name);
+    write_packet(
+        link,
+        &Packet::with_data(pc_id, Command::Req, request),
+        TRANSFER_TIMEOUT,
+    )?;
+    expect(link, Command::Ack)?;
+    // an EOT instead of the header means there's nothing by that name
+    let header = match expect(link, Command::Var) {
+        Ok(header) => header.data,
+        Err(TransferError::UnexpectedPacket(Command::Eot)) => {
+            write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+            return Err(TransferError::NotFound);
+        }
+        Err(e) => {
+            return Err(e);
+        }
+    };
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+    write_packet(link, &Packet::new(pc_id, Command::Cts), TRANSFER_TIMEOUT)?;
+    expect(link, Command::Ack)?;
+    let data = expect(link, Command::Data)?.data;
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+    expect(link, Command::Eot)?;
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+
+    // same four mystery bytes up front as when sending a variable
+    Ok((header, data.get(4..).unwrap_or_default().to_vec()))
+}
+
+// the 83+ just sends one header after another, no folders
+fn list_vars_ti83p(link: &mut dyn LinkTransport) -> Result<Vec<DirEntry>, TransferError> {
+    let pc_id = pc_machine_id(MACHINE_ID_TI83P).unwrap();
+
+    let request = var_header_ti83p(0, TI83P_DIR, "");
+    write_packet(
+        link,
+        &Packet::with_data(pc_id, Command::Req, request),
+        TRANSFER_TIMEOUT,
+    )?;
+    expect(link, Command::Ack)?;
+    // free memory, which we don't care about
+    expect(link, Command::Data)?;
+    write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+
+    let mut entries = Vec::new();
+    loop {
+        let packet = read_packet(link, TRANSFER_TIMEOUT)?;
+        write_packet(link, &Packet::new(pc_id, Command::Ack), TRANSFER_TIMEOUT)?;
+
+        match packet.command {
+            Command::Eot => break,
+            Command::Var if packet.data.len() >= 11 => {
+                entries.push(DirEntry {
+                    folder: String::new(),
+                    name: padded_string(&packet.data[3..11]),
+                    var_type: packet.data[2],
+                    attr: packet.data.get(12).copied().unwrap_or(0),
+                    size: u16::from_le_bytes([packet.data[0], packet.data[1]]) as u32,
+                });
+            }
+            command => {
+                return Err(TransferError::UnexpectedPacket(command));
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+pub(crate) fn expect(
+    link: &mut dyn LinkTransport,
+    command: Command,
+) -> Result<Packet, TransferError> {
+    let packet = read_packet(link, TRANSFER_TIMEOUT)?;
+
+    match packet.command {
+        received if received == command => Ok(packet),
+        Command::Ske => Err(TransferError::Refused),
+        received => {
+            debug_eprintln!("vars: expected {command:?}, got {received:?}");
+            Err(TransferError::UnexpectedPacket(received))
+        }
+    }
+}
+
+// [size (LE u32), type, name length, name, 0]
+fn var_header_ti89(size: u32, var_type: u8, name: &str) -> Vec<u8> {
+    let mut header = size.to_le_bytes().to_vec();
+    header.push(var_type);
+    header.push(name.len() as u8);
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header
+}
+
+// [size (LE u16), type, name padded to 8 bytes]
+fn var_header_ti83p(size: u16, var_type: u8, name: &str) -> Vec<u8> {
+    let mut header = size.to_le_bytes().to_vec();
+    header.push(var_type);
+    let mut padded_name = [0; 8];
+    for (padded, byte) in padded_name.iter_mut().zip(name.bytes()) {
+        *padded = byte;
+    }
+    header.extend_from_slice(&padded_name);
+    header
+}
+
+// folder\name, as the 89/92+ want it when it's not going to the current folder
+fn full_name(folder: &str, name: &str) -> String {
+    if folder.is_empty() {
+        name.to_string()
+    } else {
+        format!("{folder}\\{name}")
+    }
+}
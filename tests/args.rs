@@ -0,0 +1,221 @@
+use std::{path::PathBuf, time::Duration};
+
+use i68apollo::{
+    args::{
+        ApolloArgs, ArgsError, BackupCommand, ClockCommand, LinkChoice, RemoteInput, VarsCommand,
+    },
+    cable::{CableInfo, CableSelector},
+    calc::ReportFormat,
+    keyboard::CalcKey,
+    screen::RenderStyle,
+};
+
+fn parse(args: &[&str]) -> Result<ApolloArgs, ArgsError> {
+    ApolloArgs::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn cable_selectors_parse() {
+    assert_eq!(
+        parse(&["--cable", "1:5"]).unwrap().link,
+        LinkChoice::SilverLink(CableSelector::BusAddress(1, 5))
+    );
+    assert_eq!(
+        parse(&["--cable", "3-1.4"]).unwrap().link,
+        LinkChoice::SilverLink(CableSelector::PortPath("3-1.4".to_string()))
+    );
+    assert_eq!(
+        parse(&["--cable-serial", "ABC123"]).unwrap().link,
+        LinkChoice::SilverLink(CableSelector::Serial("ABC123".to_string()))
+    );
+    assert_eq!(
+        parse(&["--cable", "banana"]),
+        Err(ArgsError::InvalidValue(
+            "--cable".to_string(),
+            "banana".to_string()
+        ))
+    );
+    assert_eq!(
+        parse(&["--cable"]),
+        Err(ArgsError::MissingValue("--cable".to_string()))
+    );
+}
+
+#[test]
+fn selectors_match_cable_info() {
+    let info = CableInfo {
+        bus_number: 1,
+        address: 7,
+        port_numbers: vec![3, 2],
+        serial: None,
+    };
+
+    assert_eq!(info.port_path(), "1-3.2");
+    assert!(CableSelector::First.matches(&info));
+    assert!(CableSelector::BusAddress(1, 7).matches(&info));
+    assert!(!CableSelector::BusAddress(1, 8).matches(&info));
+    assert!(CableSelector::PortPath("1-3.2".to_string()).matches(&info));
+    assert!(!CableSelector::Serial("ABC123".to_string()).matches(&info));
+
+    // the address won't survive a replug, the port will
+    assert_eq!(
+        CableSelector::BusAddress(1, 7).for_reconnect(&info),
+        CableSelector::PortPath("1-3.2".to_string())
+    );
+}
+
+#[test]
+fn repeated_cables_select_several() {
+    let args = parse(&["--cable", "1:5", "--cable-serial", "ABC123", "--merge"]).unwrap();
+
+    assert_eq!(
+        args.link,
+        LinkChoice::SilverLinks(vec![
+            CableSelector::BusAddress(1, 5),
+            CableSelector::Serial("ABC123".to_string()),
+        ])
+    );
+    assert!(args.merge_keyboards);
+    assert_eq!(
+        parse(&["--all-cables"]).unwrap().link,
+        LinkChoice::AllSilverLinks
+    );
+}
+
+#[test]
+fn vars_subcommands_parse() {
+    let args = parse(&["--cable", "1:5", "vars", "get", "main\\notes"]).unwrap();
+    assert_eq!(
+        args.vars_command,
+        Some(VarsCommand::Get("main\\notes".to_string()))
+    );
+    assert_eq!(
+        args.link,
+        LinkChoice::SilverLink(CableSelector::BusAddress(1, 5))
+    );
+
+    assert_eq!(
+        parse(&["vars", "put", "prog.8xp"]).unwrap().vars_command,
+        Some(VarsCommand::Put(PathBuf::from("prog.8xp")))
+    );
+    assert_eq!(
+        parse(&["vars", "rm"]),
+        Err(ArgsError::MissingValue("vars rm".to_string()))
+    );
+    assert_eq!(
+        parse(&["vars", "mv"]),
+        Err(ArgsError::InvalidValue(
+            "vars".to_string(),
+            "mv".to_string()
+        ))
+    );
+}
+
+#[test]
+fn screenshots_need_a_known_format() {
+    assert_eq!(
+        parse(&["screenshot", "home.png"]).unwrap().screenshot,
+        Some(PathBuf::from("home.png"))
+    );
+    assert_eq!(
+        parse(&["screenshot", "home.jpg"]),
+        Err(ArgsError::InvalidValue(
+            "screenshot".to_string(),
+            "home.jpg".to_string()
+        ))
+    );
+}
+
+#[test]
+fn mirror_options_parse() {
+    let args = parse(&["--fps", "2", "--blocks", "mirror"]).unwrap();
+    assert!(args.mirror);
+    assert_eq!(args.mirror_fps, 2);
+    assert_eq!(args.mirror_style, RenderStyle::HalfBlocks);
+
+    assert_eq!(
+        parse(&["--fps", "0", "mirror"]),
+        Err(ArgsError::InvalidValue(
+            "--fps".to_string(),
+            "0".to_string()
+        ))
+    );
+}
+
+#[test]
+fn remote_inputs_parse() {
+    assert_eq!(
+        parse(&["type"]).unwrap().remote_input,
+        Some(RemoteInput::Stdin)
+    );
+    assert_eq!(
+        parse(&["remote", "/dev/input/event3"])
+            .unwrap()
+            .remote_input,
+        Some(RemoteInput::Evdev(PathBuf::from("/dev/input/event3")))
+    );
+}
+
+#[test]
+fn backup_commands_parse() {
+    assert_eq!(
+        parse(&["restore", "class.tar"]).unwrap().backup_command,
+        Some(BackupCommand::Restore(PathBuf::from("class.tar")))
+    );
+    assert_eq!(
+        parse(&["backup"]),
+        Err(ArgsError::MissingValue("backup".to_string()))
+    );
+}
+
+#[test]
+fn text_flag_parses() {
+    let args = parse(&["--text", "vars", "get", "hello"]).unwrap();
+    assert!(args.program_text);
+    assert_eq!(
+        args.vars_command,
+        Some(VarsCommand::Get("hello".to_string()))
+    );
+    assert!(!parse(&["vars", "ls"]).unwrap().program_text);
+}
+
+#[test]
+fn clock_commands_parse() {
+    assert_eq!(
+        parse(&["clock", "sync"]).unwrap().clock_command,
+        Some(ClockCommand::Sync)
+    );
+    assert_eq!(
+        parse(&["clock", "show"]).unwrap().clock_command,
+        Some(ClockCommand::Show)
+    );
+    assert!(matches!(
+        parse(&["clock", "wind"]),
+        Err(ArgsError::InvalidValue(_, _))
+    ));
+}
+
+#[test]
+fn session_options_parse() {
+    let args = parse(&[
+        "--quit-key",
+        "ESC",
+        "--scan-rate",
+        "30",
+        "--report-format",
+        "full",
+        "--link-timeout",
+        "5",
+    ])
+    .unwrap();
+    assert_eq!(args.session_config.quit_key, CalcKey::ESC);
+    assert_eq!(args.session_config.scan_rate, 30);
+    assert_eq!(args.session_config.report_format, ReportFormat::FullMatrix);
+    assert_eq!(args.link_timeout, Duration::from_secs(5));
+
+    assert!(parse(&["--quit-key", "Banana"]).is_err());
+    assert!(parse(&["--scan-rate", "0"]).is_err());
+    assert!(parse(&["--report-format", "sparse"]).is_err());
+    // shorter than a couple of heartbeats
+    assert!(parse(&["--link-timeout", "1"]).is_err());
+}
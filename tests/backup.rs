@@ -0,0 +1,197 @@
+mod common;
+
+use i68apollo::{
+    backup::{
+        backup, restore,
+        tar::{read_tar, write_tar},
+        Backup, BackupError,
+    },
+    formats::{TiVar, TI83P_ATTR_ARCHIVED, TI89_ATTR_ARCHIVED},
+    link::{
+        mock::MockLink,
+        packet::{Command, Packet, PC_TO_TI89, TI83P_TO_PC, TI89_TO_PC},
+    },
+    remote::TI89_KEY_ENTER,
+    vars::{DirEntry, TransferError, FLASH_APP},
+};
+
+use common::push_packet;
+
+fn var(folder: &str, name: &str, var_type: u8, data: &[u8]) -> TiVar {
+    TiVar {
+        folder: folder.to_string(),
+        name: name.to_string(),
+        var_type,
+        attr: 0,
+        version: 0,
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn tarballs_round_trip() {
+    let files = vec![
+        ("manifest.txt".to_string(), b"hello\n".to_vec()),
+        ("main/notes.89t".to_string(), vec![0xAA; 600]),
+    ];
+
+    let tar = write_tar(&files, 1_700_000_000);
+    // a header block each, then the data padded out to whole blocks, then two empty ones
+    assert_eq!(tar.len(), 512 * (1 + 1 + 1 + 2 + 2));
+    assert_eq!(&tar[257..262], b"ustar");
+    assert_eq!(read_tar(&tar), Ok(files));
+}
+
+#[test]
+fn backups_round_trip() {
+    let mut notes = var("main", "notes", 0x0B, &[0x00, 0x02, 0x00, 0xE0]);
+    notes.attr = 0x03;
+    let backup = Backup {
+        machine_id: 89,
+        os_version: Some((2, 9)),
+        vars: vec![
+            notes,
+            var("main", "x", 0x00, &[0x00, 0x01, 0x2A]),
+            var("games", "tetris", 0x12, &[0x00, 0x02, 0x19, 0xE4]),
+        ],
+        apps: vec![DirEntry {
+            folder: String::new(),
+            name: "Calculus Tools".to_string(),
+            var_type: FLASH_APP,
+            attr: 0,
+            size: 40000,
+        }],
+    };
+
+    let tar = backup.to_tar(0);
+    let files = read_tar(&tar).unwrap();
+    let paths = files
+        .iter()
+        .map(|(path, _)| path.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(
+        paths,
+        [
+            "manifest.txt",
+            "main/notes.89t",
+            "main/x.89e",
+            "games/tetris.89p"
+        ]
+    );
+    let manifest = String::from_utf8(files[0].1.clone()).unwrap();
+    assert!(manifest.contains("os version: 2.09\n"));
+    assert!(manifest.contains("var main/notes.89t TEXT 4 archived\n"));
+
+    assert_eq!(Backup::from_tar(&tar), Ok(backup));
+
+    assert_eq!(
+        Backup::from_tar(&write_tar(&files[1..], 0)),
+        Err(BackupError::NoManifest)
+    );
+}
+
+#[test]
+fn ti83p_list_names_make_file_names() {
+    // L1 and L2 are tokens, which both come out as "]" and something unprintable
+    let backup = Backup {
+        machine_id: 183,
+        os_version: None,
+        vars: vec![
+            var("", "]\u{1}", 0x01, &[0, 0]),
+            var("", "]\u{2}", 0x01, &[0, 0]),
+        ],
+        apps: Vec::new(),
+    };
+
+    let files = read_tar(&backup.to_tar(0)).unwrap();
+    assert_eq!(files[1].0, "__.8xl");
+    assert_eq!(files[2].0, "2-__.8xl");
+}
+
+#[test]
+fn ti83p_calcs_back_up() {
+    let mut link = MockLink::new();
+    // VER
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Data, vec![1, 19, 1, 0]),
+    );
+    // the directory, with one archived string in it
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Data, vec![0xFF, 0x5F]),
+    );
+    let mut header = vec![0x04, 0x00, 0x04];
+    header.extend_from_slice(b"Str1\0\0\0\0");
+    header.extend_from_slice(&[0x00, TI83P_ATTR_ARCHIVED]);
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Var, header.clone()),
+    );
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Eot));
+    // and then the string
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Var, header),
+    );
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Data, vec![0x02, 0x00, 0xBB, 0xCC]),
+    );
+
+    let backup = backup(&mut link, 183).unwrap();
+
+    assert_eq!(backup.os_version, Some((1, 19)));
+    assert_eq!(backup.vars.len(), 1);
+    assert_eq!(backup.vars[0].name, "Str1");
+    assert_eq!(backup.vars[0].attr, TI83P_ATTR_ARCHIVED);
+    assert_eq!(backup.vars[0].data, vec![0x02, 0x00, 0xBB, 0xCC]);
+    assert_eq!(link.bytes_remaining(), 0);
+}
+
+#[test]
+fn restores_go_on_past_refusals_and_rearchive() {
+    let mut notes = var("main", "notes", 0x0B, &[0x00, 0x02, 0x00, 0xE0]);
+    notes.attr = TI89_ATTR_ARCHIVED;
+    let backup = Backup {
+        machine_id: 89,
+        os_version: None,
+        vars: vec![
+            var("main", "x", 0x00, &[0x00, 0x01, 0x2A]),
+            var("main", "locked", 0x00, &[0x00, 0x01, 0x2A]),
+            notes,
+        ],
+        apps: Vec::new(),
+    };
+
+    let mut link = MockLink::new();
+    for command in [Command::Ack, Command::Cts, Command::Ack, Command::Ack] {
+        push_packet(&mut link, Packet::new(TI89_TO_PC, command));
+    }
+    push_packet(&mut link, Packet::new(TI89_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI89_TO_PC, Command::Ske, vec![0x01, 0x01, 0x00]),
+    );
+    for command in [Command::Ack, Command::Cts, Command::Ack, Command::Ack] {
+        push_packet(&mut link, Packet::new(TI89_TO_PC, command));
+    }
+    // then it goes back in the archive, typed at the home screen: HOME, CLEAR, CLEAR, the command and ENTER
+    for _ in 0..3 + "archive main\\notes".len() + 1 {
+        push_packet(&mut link, Packet::new(TI89_TO_PC, Command::Ack));
+    }
+
+    assert_eq!(
+        restore(&mut link, 89, &backup),
+        [Ok(()), Err(TransferError::Refused), Ok(())]
+    );
+    assert_eq!(link.bytes_remaining(), 0);
+    assert!(link
+        .written()
+        .ends_with(&Packet::with_param(PC_TO_TI89, Command::Cmd, TI89_KEY_ENTER).encode()));
+}
@@ -0,0 +1,39 @@
+use i68apollo::{cable::CableIoError, link::LinkIoError};
+
+#[test]
+fn usb_errors_map_to_cable_errors() {
+    assert_eq!(
+        CableIoError::from(rusb::Error::Timeout),
+        CableIoError::Timeout
+    );
+    assert_eq!(
+        CableIoError::from(rusb::Error::NoDevice),
+        CableIoError::Disconnected
+    );
+    assert_eq!(CableIoError::from(rusb::Error::Pipe), CableIoError::Stall);
+    assert_eq!(
+        CableIoError::from(rusb::Error::Overflow),
+        CableIoError::Overflow
+    );
+    assert_eq!(
+        CableIoError::from(rusb::Error::Io),
+        CableIoError::Other(rusb::Error::Io)
+    );
+}
+
+#[test]
+fn only_transient_link_errors_are_retried() {
+    let retryable = [CableIoError::Timeout, CableIoError::Stall];
+    let fatal = [
+        CableIoError::Disconnected,
+        CableIoError::Overflow,
+        CableIoError::Other(rusb::Error::Io),
+    ];
+
+    for e in retryable {
+        assert!(LinkIoError::from(e).is_retryable(), "{e:?}");
+    }
+    for e in fatal {
+        assert!(!LinkIoError::from(e).is_retryable(), "{e:?}");
+    }
+}
@@ -0,0 +1,71 @@
+// each test binary only uses some of what's in here
+#![allow(dead_code)]
+
+use std::collections::HashSet;
+
+use i68apollo::{
+    keyboard::{CalcKey, KeySink},
+    link::{
+        mock::MockLink,
+        packet::{Command, Packet, TI89_TO_PC},
+    },
+};
+
+// stands in for the uinput keyboard so the tests can see exactly what apollo would have typed
+#[derive(Default)]
+pub struct RecordingKeySink {
+    pub events: Vec<(CalcKey, bool)>,
+    pub held_keys: HashSet<CalcKey>,
+    pub syncs: usize,
+}
+impl KeySink for RecordingKeySink {
+    fn press_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        self.events.push((*key, true));
+        self.held_keys.insert(*key);
+        Ok(())
+    }
+
+    fn release_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        self.events.push((*key, false));
+        self.held_keys.remove(key);
+        Ok(())
+    }
+
+    fn sync(&mut self) -> uinput::Result<()> {
+        self.syncs += 1;
+        Ok(())
+    }
+
+    fn release_all_keys(&mut self) -> uinput::Result<()> {
+        for key in self.held_keys.drain() {
+            self.events.push((key, false));
+        }
+        self.sync()
+    }
+}
+
+// queues up what the calculator would say, exactly as it'd come over the wire
+pub fn push_packet(link: &mut MockLink, packet: Packet) {
+    link.push_bytes(&packet.encode());
+}
+
+// everything a TI-89 says while handing over one directory listing, entries being (name, type, attribute, size)
+pub fn push_dir_ti89(link: &mut MockLink, entries: &[(&str, u8, u8, u32)]) {
+    let mut listing = vec![0; 4];
+    for (name, var_type, attr, size) in entries {
+        let mut entry = name.as_bytes().to_vec();
+        entry.resize(8, 0);
+        entry.extend_from_slice(&[*var_type, *attr]);
+        entry.extend_from_slice(&size.to_le_bytes());
+        listing.extend_from_slice(&entry);
+    }
+
+    push_packet(link, Packet::new(TI89_TO_PC, Command::Ack));
+    push_packet(
+        link,
+        Packet::with_data(TI89_TO_PC, Command::Var, vec![0; 6]),
+    );
+    push_packet(link, Packet::new(TI89_TO_PC, Command::Ack));
+    push_packet(link, Packet::with_data(TI89_TO_PC, Command::Data, listing));
+    push_packet(link, Packet::new(TI89_TO_PC, Command::Eot));
+}
@@ -0,0 +1,151 @@
+use i68apollo::{
+    formats::{FilePadding, FormatError, TiVar, VarFile},
+    link::packet::checksum,
+};
+
+// hand-assembled samples, one of each shape of file, laid out after TiLP's and TI Connect's documented formats. None of
+// them were actually saved by either, so they only check the format as it's understood here. Files that were should
+// go in alongside them once there are some to hand
+const NOTES_89T: &[u8] = include_bytes!("samples/notes.89t");
+const GROUP_9XG: &[u8] = include_bytes!("samples/group.9xg");
+const HELLO_8XP: &[u8] = include_bytes!("samples/HELLO.8xp");
+const GROUP_8XG: &[u8] = include_bytes!("samples/group.8xg");
+// from before the 83+ had flash, so no version or flag
+const A_8XN: &[u8] = include_bytes!("samples/A.8xn");
+
+#[test]
+fn samples_round_trip() {
+    for sample in [NOTES_89T, GROUP_9XG, HELLO_8XP, GROUP_8XG, A_8XN] {
+        assert_eq!(VarFile::parse(sample).unwrap().to_bytes(), sample);
+    }
+}
+
+#[test]
+fn single_files_parse() {
+    let notes = VarFile::parse(NOTES_89T).unwrap();
+    assert_eq!(notes.machine_id, 89);
+    assert_eq!(notes.folder, "main");
+    assert_eq!(notes.comment, "Single file dated 10/18/26");
+    assert_eq!(notes.file_name(), "notes.89t");
+    let var = notes.into_single().unwrap();
+    assert_eq!(
+        (
+            var.folder.as_str(),
+            var.name.as_str(),
+            var.var_type,
+            var.attr
+        ),
+        ("main", "notes", 0x0B, 0x03)
+    );
+    assert_eq!(&var.data[0..2], &[0x00, 0x16]);
+
+    let hello = VarFile::parse(HELLO_8XP).unwrap();
+    assert_eq!(hello.machine_id, 183);
+    assert_eq!(hello.file_name(), "HELLO.8xp");
+    assert_eq!(
+        hello.vars,
+        vec![TiVar {
+            folder: String::new(),
+            name: "HELLO".to_string(),
+            var_type: 0x05,
+            attr: 0x80,
+            version: 0,
+            data: vec![0x04, 0x00, 0xDE, 0x2A, 0x48, 0x2A],
+        }]
+    );
+
+    let a = VarFile::parse(A_8XN).unwrap();
+    assert!(a.short_headers);
+    assert_eq!(a.vars[0].name, "A");
+}
+
+#[test]
+fn group_files_parse() {
+    let group = VarFile::parse(GROUP_9XG).unwrap();
+    assert_eq!(group.machine_id, 192);
+    assert_eq!(group.file_name(), "group.9xg");
+    let names = group
+        .vars
+        .iter()
+        .map(|var| format!("{}\\{}", var.folder, var.name))
+        .collect::<Vec<String>>();
+    assert_eq!(names, ["main\\notes", "main\\x", "games\\tetris"]);
+    assert_eq!(group.vars[2].attr, 0x01);
+    assert_eq!(group.clone().into_single(), Err(FormatError::GroupFile));
+
+    let group = VarFile::parse(GROUP_8XG).unwrap();
+    assert_eq!(group.file_name(), "group.8xg");
+    assert_eq!(group.vars.len(), 2);
+    assert_eq!(group.vars[1].name, "A");
+    assert_eq!(group.vars[1].data[2], 0x42);
+}
+
+#[test]
+fn written_files_parse_back() {
+    let group = VarFile::parse(GROUP_9XG).unwrap();
+    let rebuilt = VarFile::group(89, "rebuilt", group.vars.clone());
+    assert_eq!(VarFile::parse(&rebuilt.to_bytes()), Ok(rebuilt));
+
+    // a lone variable outside the header's folder still needs its folder entry
+    let mut tetris = group.vars[2].clone();
+    tetris.attr = 0;
+    let mut file = VarFile::single(89, "", tetris);
+    file.folder = "main".to_string();
+    assert_eq!(VarFile::parse(&file.to_bytes()), Ok(file));
+}
+
+#[test]
+fn filler_bytes_come_back_as_they_were() {
+    // what other software might leave in the bytes that nothing reads
+    let mut notes = NOTES_89T.to_vec();
+    notes[0x08] = 0x02;
+    notes[0x12 + 30] = b'x'; // past the end of the comment
+    notes[0x3C + 14] = 0x5A; // the end of the variable's table entry
+    notes[0x52] = 0x01; // in front of its data
+    let file = VarFile::parse(&notes).unwrap();
+    assert_eq!(file.comment, "Single file dated 10/18/26");
+    assert_eq!(file.to_bytes(), notes);
+
+    // a comment that's been changed doesn't pick up what was past the end of the old one
+    let mut renamed = file.clone();
+    renamed.comment = "notes".to_string();
+    let mut comment = b"notes".to_vec();
+    comment.resize(40, 0);
+    assert_eq!(renamed.to_bytes()[0x12..0x3A], comment);
+
+    let mut hello = HELLO_8XP.to_vec();
+    hello[0x0A] = 0x01;
+    hello[0x0B + 40] = b'x';
+    hello[0x37 + 5 + 6] = b'Z'; // past the end of the name
+    let section_end = hello.len() - 2;
+    let section_checksum = checksum(&hello[0x37..section_end]);
+    hello[section_end..].copy_from_slice(&section_checksum.to_le_bytes());
+    let file = VarFile::parse(&hello).unwrap();
+    assert_eq!(file.vars[0].name, "HELLO");
+    assert_eq!(file.to_bytes(), hello);
+
+    // and files with nothing unusual in them don't carry any of it around
+    assert_eq!(
+        VarFile::parse(NOTES_89T).unwrap().padding,
+        FilePadding::default()
+    );
+}
+
+#[test]
+fn damaged_files_are_rejected() {
+    let mut corrupted = GROUP_9XG.to_vec();
+    *corrupted.last_mut().unwrap() ^= 0xff;
+    assert!(matches!(
+        VarFile::parse(&corrupted),
+        Err(FormatError::BadChecksum(_, _))
+    ));
+
+    assert_eq!(
+        VarFile::parse(&GROUP_8XG[..GROUP_8XG.len() - 4]),
+        Err(FormatError::Truncated)
+    );
+    assert_eq!(
+        VarFile::parse(b"**TI73**\x1a\x0a\x00"),
+        Err(FormatError::UnknownSignature)
+    );
+}
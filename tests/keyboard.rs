@@ -0,0 +1,52 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use i68apollo::keyboard::{CalcKey, KeySink, MergedKeyboard, SharedKeyboard};
+
+use common::RecordingKeySink;
+
+// the merged keyboard takes ownership of what it types into, so the recording has to be shared to be looked at after
+struct SharedRecording(Arc<Mutex<RecordingKeySink>>);
+impl KeySink for SharedRecording {
+    fn press_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        self.0.lock().unwrap().press_key(key)
+    }
+
+    fn release_key(&mut self, key: &CalcKey) -> uinput::Result<()> {
+        self.0.lock().unwrap().release_key(key)
+    }
+
+    fn sync(&mut self) -> uinput::Result<()> {
+        self.0.lock().unwrap().sync()
+    }
+
+    fn release_all_keys(&mut self) -> uinput::Result<()> {
+        self.0.lock().unwrap().release_all_keys()
+    }
+}
+
+#[test]
+fn merged_keys_stay_down_until_every_calculator_lets_go() {
+    let recording = Arc::new(Mutex::new(RecordingKeySink::default()));
+    let merged_kbd = Arc::new(Mutex::new(MergedKeyboard::new(Box::new(SharedRecording(
+        recording.clone(),
+    )))));
+    let mut first = SharedKeyboard::new(merged_kbd.clone());
+    let mut second = SharedKeyboard::new(merged_kbd);
+
+    first.press_key(&CalcKey::Shift).unwrap();
+    second.press_key(&CalcKey::Shift).unwrap();
+    second.press_key(&CalcKey::A).unwrap();
+    first.release_key(&CalcKey::Shift).unwrap();
+    assert_eq!(
+        recording.lock().unwrap().events,
+        vec![(CalcKey::Shift, true), (CalcKey::A, true)]
+    );
+
+    // the second calculator's link drops
+    second.release_all_keys().unwrap();
+    let recording = recording.lock().unwrap();
+    assert_eq!(recording.events.len(), 4);
+    assert!(recording.held_keys.is_empty());
+}
@@ -0,0 +1,558 @@
+mod common;
+
+use std::time::Duration;
+
+use i68apollo::{
+    calc::{
+        apollo_version, delta_record, encode_frame, ti83p, ti89, ti92p, CalcHandle, HandshakeError,
+        I68MetaInfo, ReportFormat, SessionConfig, DELTA_KEYFRAME, FLAG_FRAMED, FRAME_START,
+    },
+    keyboard::CalcKey,
+    link::{mock::MockLink, LinkIoError, LinkTransport},
+    run, RunConfig,
+};
+
+use common::RecordingKeySink;
+
+fn matching_soyuz_ver() -> (u8, u8, u8) {
+    let (major, minor, _) = apollo_version();
+    (major, minor, 0)
+}
+
+#[test]
+fn handshake_detects_each_model() {
+    for machine_id in [89, 192, 183] {
+        let mut link = MockLink::new();
+        link.push_handshake(matching_soyuz_ver(), machine_id);
+
+        let meta = I68MetaInfo::handshake(&mut link).unwrap();
+
+        assert_eq!(meta.machine_id, machine_id);
+        assert_eq!(meta.soyuz_ver, matching_soyuz_ver());
+        assert_eq!(link.bytes_remaining(), 0);
+    }
+}
+
+#[test]
+fn handshake_sends_apollo_version() {
+    let mut link = MockLink::new();
+    link.push_handshake(matching_soyuz_ver(), 89);
+
+    I68MetaInfo::handshake(&mut link).unwrap();
+
+    let (major, minor, patch) = apollo_version();
+    assert_eq!(&link.written()[..3], &[major, minor, patch]);
+}
+
+#[test]
+fn handshake_negotiates_config() {
+    let requested = SessionConfig {
+        quit_key: CalcKey::ESC,
+        report_format: ReportFormat::FullMatrix,
+        scan_rate: 30,
+        flags: 0b11,
+    };
+    // soyuz will only scan half as often, and only does one of the features
+    let answer = SessionConfig {
+        scan_rate: 15,
+        flags: 0b01,
+        ..requested
+    };
+
+    let mut link = MockLink::new();
+    let (major, minor, _) = apollo_version();
+    link.push_bytes(&[0x50, major, minor, 0, 89]);
+    link.push_bytes(&answer.encode(89).unwrap());
+
+    let meta = I68MetaInfo::handshake_with(&mut link, &requested).unwrap();
+
+    assert_eq!(meta.config, answer);
+    // ESC is on row 6, bit 0
+    assert_eq!(&link.written()[3..], &[6, 0, 0, 30, 0b11]);
+}
+
+#[test]
+fn handshake_rejects_config_that_wasnt_asked_for() {
+    let mut link = MockLink::new();
+    let (major, minor, _) = apollo_version();
+    link.push_bytes(&[0x50, major, minor, 0, 89]);
+    link.push_bytes(&[6, 7, 0, 20, 0x80]);
+
+    assert_eq!(
+        I68MetaInfo::handshake(&mut link).err(),
+        Some(HandshakeError::BadConfig([6, 7, 0, 20, 0x80]))
+    );
+
+    // and apollo can't ask for a quit key the calculator doesn't have
+    let mut link = MockLink::new();
+    link.push_bytes(&[0x50, major, minor, 0, 89]);
+    let requested = SessionConfig {
+        quit_key: CalcKey::Hand,
+        ..SessionConfig::default()
+    };
+
+    assert_eq!(
+        I68MetaInfo::handshake_with(&mut link, &requested).err(),
+        Some(HandshakeError::NoSuchKey(CalcKey::Hand))
+    );
+}
+
+#[test]
+fn handshake_rejects_bad_ready_byte() {
+    let mut link = MockLink::new();
+    link.push_bytes(&[0x00]);
+
+    let result = I68MetaInfo::handshake(&mut link);
+
+    assert_eq!(result.err(), Some(HandshakeError::OtherError));
+}
+
+#[test]
+fn handshake_rejects_version_mismatch() {
+    let (major, minor, _) = apollo_version();
+    let mut link = MockLink::new();
+    link.push_handshake((major, minor.wrapping_add(1), 7), 89);
+
+    let result = I68MetaInfo::handshake(&mut link);
+
+    assert_eq!(
+        result.err(),
+        Some(HandshakeError::VersionMismatch(
+            major,
+            minor.wrapping_add(1),
+            7
+        ))
+    );
+}
+
+#[test]
+fn handshake_rejects_unknown_machine_id() {
+    let mut link = MockLink::new();
+    link.push_handshake(matching_soyuz_ver(), 42);
+
+    let result = I68MetaInfo::handshake(&mut link);
+
+    assert_eq!(result.err(), Some(HandshakeError::UnknownMachineId(42)));
+}
+
+#[test]
+fn handshake_reports_dropped_link() {
+    let mut link = MockLink::new();
+    link.push_bytes(&[0x50, 0x00]);
+
+    let result = I68MetaInfo::handshake(&mut link);
+
+    assert_eq!(
+        result.err(),
+        Some(HandshakeError::LinkError(LinkIoError::Disconnected))
+    );
+}
+
+#[test]
+fn ti89_reports_only_changed_keys() {
+    let mut link = MockLink::new();
+    let mut pressed = [0; ti89::KEY_MATRIX_LEN];
+    pressed[4] = 1 << 1; // 1
+    pressed[0] = 1 << 5; // Shift
+    link.push_frame(&pressed);
+    link.push_frame(&pressed);
+    link.push_frame(&[0; ti89::KEY_MATRIX_LEN]);
+
+    let mut calc = ti89::TI89::new();
+
+    let first = calc.get_keys(&mut link, Duration::ZERO).unwrap();
+    assert_eq!(first, vec![(CalcKey::Shift, true), (CalcKey::_1, true)]);
+
+    assert_eq!(calc.get_keys(&mut link, Duration::ZERO).unwrap(), vec![]);
+
+    let released = calc.get_keys(&mut link, Duration::ZERO).unwrap();
+    assert_eq!(
+        released,
+        vec![(CalcKey::Shift, false), (CalcKey::_1, false)]
+    );
+}
+
+#[test]
+fn ti92p_and_ti83p_decode_their_own_layouts() {
+    let mut link = MockLink::new();
+    let mut frame = [0; ti92p::KEY_MATRIX_LEN];
+    frame[9] = 1 << 2; // A
+    link.push_frame(&frame);
+    let mut calc = ti92p::TI92Plus::new();
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::A, true)]
+    );
+
+    let mut link = MockLink::new();
+    let mut frame = [0; ti83p::KEY_MATRIX_LEN];
+    frame[1] = 1; // ENTER
+    link.push_frame(&frame);
+    let mut calc = ti83p::TI83Plus::new();
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::ENTER1, true)]
+    );
+}
+
+#[test]
+fn frames_with_stray_bits_count_as_malformed() {
+    let mut link = MockLink::new();
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[6] = 1 << 3; // nothing there on a TI-89
+    frame[5] = 1 << 5; // X
+    link.push_frame(&frame);
+    link.push_frame(&[0; ti89::KEY_MATRIX_LEN]);
+
+    let mut calc = ti89::TI89::new();
+
+    // the keys that are real still get through
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::X, true)]
+    );
+    calc.get_keys(&mut link, Duration::ZERO).unwrap();
+
+    assert_eq!(link.stats().frames_read, 2);
+    assert_eq!(link.stats().malformed_reads, 1);
+}
+
+#[test]
+fn delta_reports_decode() {
+    let mut link = MockLink::new();
+    let mut keyframe = vec![DELTA_KEYFRAME; 1 + ti89::KEY_MATRIX_LEN];
+    keyframe[1..].fill(0);
+    keyframe[1 + 5] = 1 << 5; // X
+    link.push_frame(&keyframe);
+    link.push_frame(&[2, delta_record(5, 5, false), delta_record(6, 0, true)]);
+    // row 6 bit 3 isn't a key on a TI-89
+    link.push_frame(&[1, delta_record(6, 3, true)]);
+    link.push_frame(&[0]);
+
+    let mut calc = ti89::TI89::with_config(&SessionConfig {
+        report_format: ReportFormat::Delta,
+        flags: 0,
+        ..SessionConfig::default()
+    });
+
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::X, true)]
+    );
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::X, false), (CalcKey::ESC, true)]
+    );
+    assert_eq!(calc.get_keys(&mut link, Duration::ZERO).unwrap(), vec![]);
+    assert_eq!(calc.get_keys(&mut link, Duration::ZERO).unwrap(), vec![]);
+
+    assert_eq!(link.stats().frames_read, 4);
+    assert_eq!(link.stats().malformed_reads, 1);
+    // against 28 for the same four as full matrices
+    assert_eq!(link.stats().bytes_read_overall, 14);
+}
+
+#[test]
+fn delta_reports_split_across_a_timeout_arent_lost() {
+    let mut link = MockLink::new();
+    link.go_quiet();
+    link.push_frame(&[1]);
+
+    let mut calc = ti89::TI89::with_config(&SessionConfig {
+        report_format: ReportFormat::Delta,
+        flags: 0,
+        ..SessionConfig::default()
+    });
+
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO),
+        Err(LinkIoError::Timeout)
+    );
+    link.push_frame(&[delta_record(6, 0, true)]);
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::ESC, true)]
+    );
+    assert_eq!(link.stats().malformed_reads, 0);
+}
+
+#[test]
+fn framed_reports_resync_after_damage() {
+    let mut x = [0; ti89::KEY_MATRIX_LEN];
+    x[5] = 1 << 5; // X
+    let mut esc = [0; ti89::KEY_MATRIX_LEN];
+    esc[6] = 1; // ESC
+
+    let mut link = MockLink::new();
+    link.push_bytes(&encode_frame(0, &x));
+    // a byte from the middle of something, then a frame with a byte flipped in the middle
+    link.push_bytes(&[0x42]);
+    let mut damaged = encode_frame(1, &[0; ti89::KEY_MATRIX_LEN]);
+    damaged[5] ^= 0x10;
+    link.push_bytes(&damaged);
+    link.push_bytes(&encode_frame(2, &esc));
+    // too short to be a key matrix
+    link.push_bytes(&encode_frame(3, &[0; 2]));
+
+    let mut calc = ti89::TI89::with_config(&SessionConfig {
+        report_format: ReportFormat::FullMatrix,
+        flags: FLAG_FRAMED,
+        ..SessionConfig::default()
+    });
+
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::X, true)]
+    );
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::X, false), (CalcKey::ESC, true)]
+    );
+    assert_eq!(calc.get_keys(&mut link, Duration::ZERO).unwrap(), vec![]);
+    assert_eq!(link.bytes_remaining(), 0);
+
+    // the stray byte and the damaged frame count as one, being skipped in one go, and the short one as another
+    assert_eq!(link.stats().malformed_reads, 2);
+}
+
+#[test]
+fn missing_delta_frames_let_go_of_every_key() {
+    let mut keyframe = vec![0; 1 + ti89::KEY_MATRIX_LEN];
+    keyframe[0] = DELTA_KEYFRAME;
+    keyframe[1] = 1 << 5; // Shift
+    keyframe[1 + 5] = 1 << 5; // X
+
+    let mut link = MockLink::new();
+    link.push_bytes(&encode_frame(0, &keyframe));
+    // frame 1, letting go of X, never makes it
+    link.push_bytes(&encode_frame(2, &[1, delta_record(6, 0, true)]));
+    let mut next_keyframe = keyframe.clone();
+    next_keyframe[1 + 5] = 0;
+    next_keyframe[1 + 6] = 1; // ESC
+    link.push_bytes(&encode_frame(3, &next_keyframe));
+
+    let mut calc = ti89::TI89::with_config(&SessionConfig {
+        report_format: ReportFormat::Delta,
+        flags: FLAG_FRAMED,
+        ..SessionConfig::default()
+    });
+
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::Shift, true), (CalcKey::X, true)]
+    );
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![
+            (CalcKey::Shift, false),
+            (CalcKey::X, false),
+            (CalcKey::ESC, true)
+        ]
+    );
+    // Shift was held the whole time, so the keyframe puts it back
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::Shift, true)]
+    );
+
+    assert_eq!(link.stats().frames_read, 3);
+    assert_eq!(link.stats().malformed_reads, 1);
+}
+
+#[test]
+fn framed_deltas_can_be_longer_than_a_keyframe() {
+    // two whole rows of a TI-89 going down at once
+    let changes = (1..=2)
+        .flat_map(|row| (0..8).map(move |col| delta_record(row, col, true)))
+        .collect::<Vec<u8>>();
+    assert!(1 + changes.len() > 1 + ti89::KEY_MATRIX_LEN);
+
+    let mut link = MockLink::new();
+    link.push_bytes(&encode_frame(
+        0,
+        &[[changes.len() as u8].as_slice(), &changes].concat(),
+    ));
+
+    let mut calc = ti89::TI89::with_config(&SessionConfig {
+        report_format: ReportFormat::Delta,
+        flags: FLAG_FRAMED,
+        ..SessionConfig::default()
+    });
+
+    assert_eq!(calc.get_keys(&mut link, Duration::ZERO).unwrap().len(), 16);
+    assert_eq!(link.bytes_remaining(), 0);
+    assert_eq!(link.stats().malformed_reads, 0);
+}
+
+#[test]
+fn framed_reports_with_impossible_lengths_are_skipped() {
+    let mut x = [0; ti89::KEY_MATRIX_LEN];
+    x[5] = 1 << 5; // X
+
+    let mut link = MockLink::new();
+    // a start byte that isn't, going by the length after it, which would otherwise swallow the real frame
+    link.push_bytes(&[FRAME_START, 0, 0xC8]);
+    link.push_bytes(&encode_frame(0, &x));
+
+    let mut calc = ti89::TI89::with_config(&SessionConfig {
+        report_format: ReportFormat::FullMatrix,
+        flags: FLAG_FRAMED,
+        ..SessionConfig::default()
+    });
+
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::ZERO).unwrap(),
+        vec![(CalcKey::X, true)]
+    );
+    assert_eq!(link.bytes_remaining(), 0);
+    assert_eq!(link.stats().malformed_reads, 1);
+}
+
+#[test]
+fn handshake_accepts_full_matrices_instead_of_deltas() {
+    let mut link = MockLink::new();
+    link.push_handshake(matching_soyuz_ver(), 89);
+
+    let meta = I68MetaInfo::handshake(&mut link).unwrap();
+
+    assert_eq!(SessionConfig::default().report_format, ReportFormat::Delta);
+    assert_eq!(meta.config.report_format, ReportFormat::FullMatrix);
+}
+
+#[test]
+fn run_forwards_keys_and_quits_on_on() {
+    let mut link = MockLink::new();
+    link.push_handshake(matching_soyuz_ver(), 89);
+
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[5] = 1 << 5; // X
+    link.push_frame(&frame);
+    link.push_frame(&[0; ti89::KEY_MATRIX_LEN]);
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[6] = 1 << 7; // ON
+    link.push_frame(&frame);
+    // anything past ON mustn't make it to the keyboard
+    link.push_frame(&[0xff; ti89::KEY_MATRIX_LEN]);
+
+    let mut meta = I68MetaInfo::handshake(&mut link).unwrap();
+    let mut sink = RecordingKeySink::default();
+
+    run(
+        &mut link,
+        meta.calc_handle.as_mut(),
+        &mut sink,
+        &RunConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(sink.events, vec![(CalcKey::X, true), (CalcKey::X, false)]);
+    assert_eq!(sink.syncs, 2);
+    assert_eq!(link.bytes_remaining(), ti89::KEY_MATRIX_LEN);
+    assert_eq!(link.stats().latency.samples, 2);
+}
+
+#[test]
+fn run_quits_on_the_negotiated_key() {
+    let mut link = MockLink::new();
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[6] = 1 << 7; // ON
+    link.push_frame(&frame);
+    link.push_frame(&[0; ti89::KEY_MATRIX_LEN]);
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[6] = 1; // ESC
+    link.push_frame(&frame);
+    link.push_frame(&[0xff; ti89::KEY_MATRIX_LEN]);
+
+    let mut sink = RecordingKeySink::default();
+    let config = RunConfig {
+        quit_key: CalcKey::ESC,
+        ..RunConfig::default()
+    };
+
+    run(&mut link, &mut ti89::TI89::new(), &mut sink, &config).unwrap();
+
+    assert_eq!(sink.events, vec![(CalcKey::ON, true), (CalcKey::ON, false)]);
+}
+
+#[test]
+fn run_passes_up_link_errors() {
+    let mut link = MockLink::new();
+    link.push_handshake(matching_soyuz_ver(), 183);
+    link.push_frame(&[0; 3]);
+
+    let mut meta = I68MetaInfo::handshake(&mut link).unwrap();
+    let mut sink = RecordingKeySink::default();
+
+    assert_eq!(
+        run(
+            &mut link,
+            meta.calc_handle.as_mut(),
+            &mut sink,
+            &RunConfig::default()
+        ),
+        Err(LinkIoError::Disconnected)
+    );
+}
+
+#[test]
+fn run_releases_held_keys_when_link_drops() {
+    let mut link = MockLink::new();
+    link.push_handshake(matching_soyuz_ver(), 89);
+
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[0] = 1 << 5; // Shift
+    link.push_frame(&frame);
+    // and then the cable gets yanked halfway through the next frame
+    link.push_bytes(&[0; 2]);
+
+    let mut meta = I68MetaInfo::handshake(&mut link).unwrap();
+    let mut sink = RecordingKeySink::default();
+
+    assert_eq!(
+        run(
+            &mut link,
+            meta.calc_handle.as_mut(),
+            &mut sink,
+            &RunConfig::default()
+        ),
+        Err(LinkIoError::Disconnected)
+    );
+    assert_eq!(
+        sink.events,
+        vec![(CalcKey::Shift, true), (CalcKey::Shift, false)]
+    );
+    assert!(sink.held_keys.is_empty());
+}
+
+#[test]
+fn run_releases_held_keys_when_heartbeats_stop() {
+    let mut link = MockLink::new();
+    link.push_handshake(matching_soyuz_ver(), 89);
+
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[0] = 1 << 5; // Shift
+    link.push_frame(&frame);
+    // and then the calculator turns off, with the cable still plugged in
+    link.go_quiet();
+
+    let mut meta = I68MetaInfo::handshake(&mut link).unwrap();
+    let mut sink = RecordingKeySink::default();
+
+    assert_eq!(
+        run(
+            &mut link,
+            meta.calc_handle.as_mut(),
+            &mut sink,
+            &RunConfig {
+                link_timeout: Some(Duration::from_millis(250)),
+                ..RunConfig::default()
+            }
+        ),
+        Err(LinkIoError::HeartbeatLost)
+    );
+    assert_eq!(
+        sink.events,
+        vec![(CalcKey::Shift, true), (CalcKey::Shift, false)]
+    );
+    assert!(sink.held_keys.is_empty());
+}
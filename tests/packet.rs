@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use i68apollo::link::{
+    mock::MockLink,
+    packet::{self, read_packet, Command, Packet, PacketError, PC_TO_TI89, TI89_TO_PC},
+};
+
+#[test]
+fn packets_round_trip() {
+    let with_data = Packet::with_data(PC_TO_TI89, Command::Data, vec![0x12, 0x34, 0xff]);
+    let encoded = with_data.encode();
+    assert_eq!(
+        encoded,
+        vec![0x08, 0x15, 0x03, 0x00, 0x12, 0x34, 0xff, 0x45, 0x01]
+    );
+    assert_eq!(Packet::decode(&encoded), Ok((with_data, 9)));
+
+    // the keycode of a CMD rides in the length field, there's no data or checksum
+    let key = Packet::with_param(PC_TO_TI89, Command::Cmd, 264);
+    let encoded = key.encode();
+    assert_eq!(encoded, vec![0x08, 0x87, 0x08, 0x01]);
+    assert_eq!(Packet::decode(&encoded), Ok((key, 4)));
+}
+
+#[test]
+fn bad_checksums_and_unknown_commands_are_rejected() {
+    let mut encoded = Packet::with_data(TI89_TO_PC, Command::Var, vec![1, 2, 3]).encode();
+    *encoded.last_mut().unwrap() ^= 0xff;
+    assert_eq!(
+        Packet::decode(&encoded).err(),
+        Some(PacketError::BadChecksum(0xff06, 0x0006))
+    );
+
+    assert_eq!(
+        Packet::decode(&[0x98, 0x01, 0x00, 0x00]).err(),
+        Some(PacketError::UnknownCommand(0x01))
+    );
+    assert_eq!(
+        Packet::decode(&[0x98, 0x15, 0x05, 0x00, 1]).err(),
+        Some(PacketError::Truncated)
+    );
+}
+
+#[test]
+fn packets_read_off_a_link() {
+    let mut link = MockLink::new();
+    link.push_bytes(&Packet::new(TI89_TO_PC, Command::Ack).encode());
+    link.push_bytes(&Packet::with_data(TI89_TO_PC, Command::Data, vec![0xaa; 300]).encode());
+
+    let ack = read_packet(&mut link, Duration::ZERO).unwrap();
+    assert_eq!(ack.command, Command::Ack);
+
+    let data = read_packet(&mut link, Duration::ZERO).unwrap();
+    assert_eq!(data.data.len(), 300);
+    assert_eq!(packet::checksum(&data.data), (0xaa * 300) as u16);
+}
+
+#[test]
+fn skip_packets_carry_a_rejection_code() {
+    let mut link = MockLink::new();
+    // out of memory, then whatever comes next
+    link.push_bytes(&[0x98, 0x36, 0x03, 0x00, 0x03, 0x01, 0x00, 0x04, 0x00]);
+    link.push_bytes(&Packet::new(TI89_TO_PC, Command::Ack).encode());
+
+    let skip = read_packet(&mut link, Duration::ZERO).unwrap();
+    assert_eq!(skip.command, Command::Ske);
+    assert_eq!(skip.data, vec![0x03, 0x01, 0x00]);
+    assert_eq!(
+        read_packet(&mut link, Duration::ZERO).unwrap().command,
+        Command::Ack
+    );
+}
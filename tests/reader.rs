@@ -0,0 +1,169 @@
+use std::{
+    io::Write,
+    os::unix::net::UnixStream,
+    sync::{atomic::AtomicBool, mpsc},
+    thread,
+    time::Duration,
+};
+
+use i68apollo::{
+    calc::{
+        ti83p::{TI83Plus, KEY_MATRIX_LEN},
+        CalcHandle,
+    },
+    keyboard::CalcKey,
+    link::{mock::MockLink, socket::SocketLink, LinkIoError, LinkStats, LinkTransport},
+    reader::{read_link, LinkEvent, OverflowPolicy},
+    RunConfig,
+};
+
+fn keys(event: LinkEvent) -> Vec<(CalcKey, bool)> {
+    match event {
+        LinkEvent::Frame(frame) => frame.iter().map(|e| (e.key, e.pressed)).collect(),
+        LinkEvent::Error(e) => panic!("expected a frame, got {e:?}"),
+    }
+}
+
+// a `MockLink` that says when its script has run dry, which is how a test can tell the reader's been through all of it
+struct DrainedLink {
+    link: MockLink,
+    drained: mpsc::Sender<()>,
+}
+impl LinkTransport for DrainedLink {
+    fn read_bytes(
+        &mut self,
+        bytes_expected: usize,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, LinkIoError> {
+        let result = self.link.read_bytes(bytes_expected, timeout);
+        if result.is_err() {
+            let _ = self.drained.send(());
+        }
+        result
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8], timeout: Duration) -> Result<(), LinkIoError> {
+        self.link.write_bytes(bytes, timeout)
+    }
+
+    fn stats(&self) -> &LinkStats {
+        self.link.stats()
+    }
+
+    fn stats_mut(&mut self) -> &mut LinkStats {
+        self.link.stats_mut()
+    }
+}
+
+#[test]
+fn coalesce_merges_frames_instead_of_dropping_them() {
+    let mut link = MockLink::new();
+    let mut frame = [0; KEY_MATRIX_LEN];
+    frame[1] = 1; // ENTER
+    link.push_frame(&frame);
+    link.push_frame(&[0; KEY_MATRIX_LEN]);
+    frame[1] = 1 << 1; // +
+    link.push_frame(&frame);
+
+    let (sender, events) = mpsc::sync_channel(1);
+    let stop = AtomicBool::new(false);
+    let (drained_sender, drained) = mpsc::channel();
+    let mut link = DrainedLink {
+        link,
+        drained: drained_sender,
+    };
+
+    let reader = thread::spawn(move || {
+        let mut calc = TI83Plus::new();
+        read_link(
+            &mut link,
+            &mut calc,
+            sender,
+            &stop,
+            &RunConfig {
+                overflow_policy: OverflowPolicy::Coalesce,
+                ..RunConfig::default()
+            },
+        )
+    });
+
+    // nobody's reading yet, so only the first frame fits and the rest have to pile up behind it
+    drained.recv().unwrap();
+
+    assert_eq!(keys(events.recv().unwrap()), vec![(CalcKey::ENTER1, true)]);
+    assert_eq!(
+        keys(events.recv().unwrap()),
+        vec![(CalcKey::ENTER1, false), (CalcKey::Addition, true)]
+    );
+    assert_eq!(
+        events.recv().unwrap(),
+        LinkEvent::Error(LinkIoError::Disconnected)
+    );
+    assert_eq!(reader.join().unwrap(), 2);
+}
+
+#[test]
+fn reader_stops_when_asked() {
+    let mut link = MockLink::new();
+    link.push_frame(&[0; KEY_MATRIX_LEN]);
+
+    let (sender, events) = mpsc::sync_channel(1);
+    let stop = AtomicBool::new(true);
+    let mut calc = TI83Plus::new();
+
+    assert_eq!(
+        read_link(&mut link, &mut calc, sender, &stop, &RunConfig::default()),
+        0
+    );
+    assert!(events.try_recv().is_err());
+    assert_eq!(link.bytes_remaining(), KEY_MATRIX_LEN);
+}
+
+#[test]
+fn silence_past_the_link_timeout_counts_as_losing_the_link() {
+    let mut link = MockLink::new();
+    let mut frame = [0; KEY_MATRIX_LEN];
+    frame[1] = 1; // ENTER
+    link.push_frame(&frame).go_quiet();
+
+    let (sender, events) = mpsc::sync_channel(4);
+    let stop = AtomicBool::new(false);
+    let mut calc = TI83Plus::new();
+
+    read_link(
+        &mut link,
+        &mut calc,
+        sender,
+        &stop,
+        &RunConfig {
+            link_timeout: Some(Duration::from_millis(250)),
+            ..RunConfig::default()
+        },
+    );
+    assert_eq!(keys(events.recv().unwrap()), vec![(CalcKey::ENTER1, true)]);
+    assert_eq!(
+        events.recv().unwrap(),
+        LinkEvent::Error(LinkIoError::HeartbeatLost)
+    );
+}
+
+#[test]
+fn a_frame_split_across_a_poll_timeout_isnt_lost() {
+    let (stream, mut soyuz) = UnixStream::pair().unwrap();
+    let mut link = SocketLink::from_stream(stream);
+    let mut calc = TI83Plus::new();
+
+    let mut frame = [0; KEY_MATRIX_LEN];
+    frame[1] = 1; // ENTER
+    soyuz.write_all(&frame[..3]).unwrap();
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::from_millis(50)),
+        Err(LinkIoError::Timeout)
+    );
+
+    soyuz.write_all(&frame[3..]).unwrap();
+    assert_eq!(
+        calc.get_keys(&mut link, Duration::from_millis(50)),
+        Ok(vec![(CalcKey::ENTER1, true)])
+    );
+}
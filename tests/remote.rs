@@ -0,0 +1,75 @@
+use i68apollo::{
+    keyboard::CalcKey,
+    remote::{char_key, RemoteKeyboard},
+};
+
+// from input-event-codes.h
+const KEY_2: u16 = 3;
+const KEY_MINUS: u16 = 12;
+const KEY_E: u16 = 18;
+const KEY_A: u16 = 30;
+const KEY_LEFTSHIFT: u16 = 42;
+const KEY_UP: u16 = 103;
+const KEY_LEFTMETA: u16 = 125;
+
+// presses then releases each key, collecting whatever would've been sent
+fn type_keys(remote_kbd: &mut RemoteKeyboard, events: &[(u16, bool)]) -> Vec<u16> {
+    events
+        .iter()
+        .filter_map(|(code, pressed)| {
+            remote_kbd
+                .calc_key(*code)
+                .and_then(|key| remote_kbd.key_event(key, *pressed))
+        })
+        .collect()
+}
+
+#[test]
+fn pc_keys_pick_the_model_s_key() {
+    let ti89 = RemoteKeyboard::new(89);
+    let ti92p = RemoteKeyboard::new(192);
+    let ti83p = RemoteKeyboard::new(183);
+
+    // E before EE, Subtraction before Inverse
+    assert_eq!(ti89.calc_key(KEY_E), Some(CalcKey::E));
+    assert_eq!(ti83p.calc_key(KEY_MINUS), Some(CalcKey::Subtraction));
+    // the 92+'s HAND and the 83+'s ALPHA share a key, and the 83+ can't send ALPHA
+    assert_eq!(ti92p.calc_key(KEY_LEFTMETA), Some(CalcKey::Hand));
+    assert_eq!(ti83p.calc_key(KEY_LEFTMETA), None);
+}
+
+#[test]
+fn modifiers_apply_to_the_next_key() {
+    let mut ti89 = RemoteKeyboard::new(89);
+    assert_eq!(
+        type_keys(
+            &mut ti89,
+            &[
+                (KEY_A, true),
+                (KEY_A, false),
+                (KEY_LEFTSHIFT, true),
+                (KEY_A, true),
+                (KEY_UP, true),
+                (KEY_LEFTSHIFT, false),
+                (KEY_2, true),
+            ]
+        ),
+        vec![b'a' as u16, b'A' as u16, 16384 + 337, b'2' as u16]
+    );
+
+    // no modifiers over the link on the 83+, its letters are what ALPHA would type
+    let mut ti83p = RemoteKeyboard::new(183);
+    assert_eq!(
+        type_keys(&mut ti83p, &[(KEY_LEFTSHIFT, true), (KEY_A, true)]),
+        vec![0x9A]
+    );
+}
+
+#[test]
+fn text_maps_to_keys() {
+    assert_eq!(char_key(89, 'x'), Some(b'x' as u16));
+    assert_eq!(char_key(89, '\n'), Some(13));
+    assert_eq!(char_key(183, 'Z'), Some(0x9A + 25));
+    assert_eq!(char_key(183, ':'), Some(0xC6));
+    assert_eq!(char_key(183, 'z'), None);
+}
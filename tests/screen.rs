@@ -0,0 +1,83 @@
+use i68apollo::{
+    link::{
+        mock::MockLink,
+        packet::{Command, Packet, TI83P_TO_PC, TI89_TO_PC},
+    },
+    screen::{screenshot, RenderStyle, Screenshot},
+};
+
+fn push_screen(link: &mut MockLink, machine_id: u8, data: Vec<u8>) {
+    link.push_bytes(&Packet::new(machine_id, Command::Ack).encode());
+    link.push_bytes(&Packet::with_data(machine_id, Command::Data, data).encode());
+}
+
+#[test]
+fn ti89_screens_are_cropped() {
+    // the 92+-sized buffer, with the first pixel of every row lit, and everything past the 89's edge too
+    let mut data = vec![0; 30 * 128];
+    for row in data.chunks_mut(30) {
+        row[0] = 0x80;
+        row[20..].fill(0xff);
+    }
+
+    let mut link = MockLink::new();
+    push_screen(&mut link, TI89_TO_PC, data);
+
+    let screen = screenshot(&mut link, 89).unwrap();
+    assert_eq!((screen.width, screen.height), (160, 100));
+    assert_eq!(screen.bitmap.len(), 20 * 100);
+    assert!(screen.pixel(0, 99));
+    assert!(!screen.pixel(159, 0));
+    assert_eq!(link.bytes_remaining(), 0);
+
+    // SCR, then the ACK for the screen
+    let (request, len) = Packet::decode(link.written()).unwrap();
+    assert_eq!(request.command, Command::Scr);
+    let ack = Packet::decode(&link.written()[len..]).unwrap().0;
+    assert_eq!(ack.command, Command::Ack);
+}
+
+#[test]
+fn short_screens_are_rejected() {
+    let mut link = MockLink::new();
+    push_screen(&mut link, TI83P_TO_PC, vec![0; 100]);
+
+    assert!(screenshot(&mut link, 183).is_err());
+}
+
+#[test]
+fn images_encode() {
+    let screen = Screenshot {
+        width: 16,
+        height: 2,
+        bitmap: vec![0xf0, 0x00, 0x00, 0x01],
+    };
+
+    assert_eq!(screen.to_pbm(), b"P4\n16 2\n\xf0\x00\x00\x01");
+
+    let png = screen.to_png();
+    assert_eq!(&png[0..8], b"\x89PNG\r\n\x1a\n");
+    // IHDR: 16x2, 1 bit greyscale
+    assert_eq!(&png[12..16], b"IHDR");
+    assert_eq!(&png[16..29], &[0, 0, 0, 16, 0, 0, 0, 2, 1, 0, 0, 0, 0]);
+    // crc of "IEND" on its own, which every PNG ends with
+    assert_eq!(&png[png.len() - 4..], &[0xae, 0x42, 0x60, 0x82]);
+
+    // IDAT is a single stored block: a filter byte then the inverted row, for each row
+    let idat = &png[33 + 8..];
+    assert_eq!(&idat[0..7], &[0x78, 0x01, 0x01, 6, 0, 0xf9, 0xff]);
+    assert_eq!(&idat[7..13], &[0, 0x0f, 0xff, 0, 0xff, 0xfe]);
+}
+
+#[test]
+fn screens_render_as_text() {
+    // a 4x4 with the top left and bottom right pixels lit
+    let screen = Screenshot {
+        width: 4,
+        height: 4,
+        bitmap: vec![0x80, 0x00, 0x00, 0x10],
+    };
+
+    assert_eq!(screen.render(RenderStyle::Braille), "\u{2801}\u{2880}");
+    assert_eq!(screen.render(RenderStyle::HalfBlocks), "▀   \n   ▄");
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use std::{thread, time::Duration};
+
+use i68apollo::{
+    calc::{apollo_version, ti89, HandshakeError, I68MetaInfo, SessionConfig, SHUTDOWN_REQUEST},
+    keyboard::CalcKey,
+    link::mock::MockLink,
+    run, shutdown, RunConfig,
+};
+
+use common::RecordingKeySink;
+
+// the shutdown flag is global, so everything that depends on it goes in the one test, in order
+#[test]
+fn shutting_down_winds_the_session_down() {
+    let (major, minor, _) = apollo_version();
+    let mut link = MockLink::new();
+    link.push_handshake((major, minor, 0), 89);
+
+    let mut frame = [0; ti89::KEY_MATRIX_LEN];
+    frame[0] = 1 << 5; // Shift
+    link.push_frame(&frame);
+    // and then nothing more from the calculator, leaving Shift held until Ctrl-C
+    link.go_quiet();
+
+    let mut meta = I68MetaInfo::handshake(&mut link).unwrap();
+    let mut sink = RecordingKeySink::default();
+
+    let interrupt = thread::spawn(|| {
+        thread::sleep(Duration::from_millis(250));
+        shutdown::request();
+    });
+    assert_eq!(
+        run(
+            &mut link,
+            meta.calc_handle.as_mut(),
+            &mut sink,
+            &RunConfig::default()
+        ),
+        Ok(())
+    );
+    interrupt.join().unwrap();
+    assert_eq!(
+        sink.events,
+        vec![(CalcKey::Shift, true), (CalcKey::Shift, false)]
+    );
+    assert!(sink.held_keys.is_empty());
+
+    // the mock's soyuz didn't agree to being told to exit, so it isn't
+    let written = link.written().len();
+    assert_eq!(meta.request_exit(&mut link), Ok(false));
+    assert_eq!(link.written().len(), written);
+
+    let mut link = MockLink::new();
+    link.push_bytes(&[0x50, major, minor, 0, 89]);
+    link.push_bytes(&SessionConfig::default().encode(89).unwrap());
+    let meta = I68MetaInfo::handshake(&mut link).unwrap();
+    assert_eq!(meta.request_exit(&mut link), Ok(true));
+    assert_eq!(link.written().last(), Some(&SHUTDOWN_REQUEST));
+
+    // still waiting on the user to start soyuz
+    let mut link = MockLink::new();
+    link.go_quiet();
+    assert!(matches!(
+        I68MetaInfo::handshake(&mut link),
+        Err(HandshakeError::Interrupted)
+    ));
+}
@@ -0,0 +1,237 @@
+mod common;
+
+use i68apollo::{
+    calc::{apollo_version, I68MetaInfo},
+    formats::{TiVar, VarFile},
+    link::{
+        mock::MockLink,
+        packet::{checksum, Command, Packet, PC_TO_TI83P, PC_TO_TI89, TI83P_TO_PC, TI89_TO_PC},
+    },
+    remote::{TI83P_KEY_ENTER, TI89_KEY_ENTER, TI89_KEY_HOME},
+    soyuz::{install, launch, InstallOutcome},
+    vars::TI89_DIR,
+};
+
+use common::{push_dir_ti89, push_packet};
+
+const PROTECTED_PROGRAM: u8 = 0x06;
+
+fn soyuz_83p() -> TiVar {
+    TiVar {
+        folder: String::new(),
+        name: "I68SOYUZ".to_string(),
+        var_type: PROTECTED_PROGRAM,
+        attr: 0,
+        version: 0,
+        data: vec![0x04, 0x00, 0xbb, 0x6d, 0xc9, 0x00],
+    }
+}
+
+// laid out the same way TI Connect writes them
+fn ti83p_file(var: &TiVar) -> Vec<u8> {
+    let mut section = vec![0x0d, 0x00];
+    section.extend_from_slice(&(var.data.len() as u16).to_le_bytes());
+    section.push(var.var_type);
+    let mut name = var.name.as_bytes().to_vec();
+    name.resize(8, 0);
+    section.extend_from_slice(&name);
+    section.extend_from_slice(&[var.version, var.attr]);
+    section.extend_from_slice(&(var.data.len() as u16).to_le_bytes());
+    section.extend_from_slice(&var.data);
+
+    let mut file = b"**TI83F*\x1a\x0a\x00".to_vec();
+    let mut comment = b"i68soyuz".to_vec();
+    comment.resize(42, 0);
+    file.extend_from_slice(&comment);
+    file.extend_from_slice(&(section.len() as u16).to_le_bytes());
+    file.extend_from_slice(&section);
+    file.extend_from_slice(&checksum(&section).to_le_bytes());
+    file
+}
+
+fn dir_entry_83p(name: &str, var_type: u8, size: u16) -> Packet {
+    let mut header = size.to_le_bytes().to_vec();
+    header.push(var_type);
+    let mut name = name.as_bytes().to_vec();
+    name.resize(8, 0);
+    header.extend_from_slice(&name);
+    Packet::with_data(TI83P_TO_PC, Command::Var, header)
+}
+
+// an 83+ with an I68SOYUZ the same size as ours on it, handing it over when asked
+fn push_same_size_soyuz_83p(link: &mut MockLink, data: Vec<u8>) {
+    push_packet(link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        link,
+        Packet::with_data(TI83P_TO_PC, Command::Data, vec![0x00, 0x60]),
+    );
+    push_packet(link, dir_entry_83p("I68SOYUZ", PROTECTED_PROGRAM, 6));
+    push_packet(link, Packet::new(TI83P_TO_PC, Command::Eot));
+
+    push_packet(link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        link,
+        Packet::with_data(TI83P_TO_PC, Command::Var, vec![0; 13]),
+    );
+    push_packet(link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(link, Packet::with_data(TI83P_TO_PC, Command::Data, data));
+}
+
+#[test]
+fn ti83p_files_parse() {
+    let file = VarFile::parse(&ti83p_file(&soyuz_83p())).unwrap();
+
+    assert_eq!(file.machine_id, 183);
+    assert_eq!(file.comment, "i68soyuz");
+    assert_eq!(file.vars, vec![soyuz_83p()]);
+
+    let mut corrupted = ti83p_file(&soyuz_83p());
+    corrupted[0x40] ^= 1;
+    assert!(VarFile::parse(&corrupted).is_err());
+}
+
+#[test]
+fn stale_soyuz_gets_replaced() {
+    let mut link = MockLink::new();
+    // the directory, with an older, smaller soyuz on it
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Data, vec![0x00, 0x60]),
+    );
+    push_packet(&mut link, dir_entry_83p("I68SOYUZ", PROTECTED_PROGRAM, 4));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Eot));
+    // and its answers to the new one coming in
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Cts));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+
+    assert_eq!(
+        install(&mut link, 183, &soyuz_83p()),
+        Ok(InstallOutcome::Updated)
+    );
+    assert_eq!(link.bytes_remaining(), 0);
+
+    let sent_data = Packet::with_data(PC_TO_TI83P, Command::Data, soyuz_83p().data).encode();
+    assert!(link
+        .written()
+        .windows(sent_data.len())
+        .any(|window| window == sent_data));
+}
+
+#[test]
+fn current_soyuz_is_left_alone() {
+    let mut link = MockLink::new();
+    push_same_size_soyuz_83p(&mut link, soyuz_83p().data);
+
+    assert_eq!(
+        install(&mut link, 183, &soyuz_83p()),
+        Ok(InstallOutcome::AlreadyInstalled)
+    );
+    assert_eq!(link.bytes_remaining(), 0);
+}
+
+#[test]
+fn same_size_soyuz_still_gets_replaced_if_it_differs() {
+    let mut link = MockLink::new();
+    let mut stale = soyuz_83p().data;
+    stale[2] = 0xef;
+    push_same_size_soyuz_83p(&mut link, stale);
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Cts));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+
+    assert_eq!(
+        install(&mut link, 183, &soyuz_83p()),
+        Ok(InstallOutcome::Updated)
+    );
+    assert_eq!(link.bytes_remaining(), 0);
+}
+
+#[test]
+fn launch_types_soyuz_call_on_ti89() {
+    let mut link = MockLink::new();
+    push_dir_ti89(&mut link, &[("main", TI89_DIR, 0, 0)]);
+    push_dir_ti89(
+        &mut link,
+        &[("main", TI89_DIR, 0, 0), ("i68soyuz", 0x21, 0, 1234)],
+    );
+    let call = "main\\i68soyuz()";
+    // one ACK per key: HOME, CLEAR twice, the call, ENTER
+    for _ in 0..3 + call.len() + 1 {
+        push_packet(&mut link, Packet::new(TI89_TO_PC, Command::Ack));
+    }
+
+    launch(&mut link, 89, None).unwrap();
+    assert_eq!(link.bytes_remaining(), 0);
+
+    let key = |keycode: u16| Packet::with_param(PC_TO_TI89, Command::Cmd, keycode).encode();
+    let keys = [TI89_KEY_HOME, b'\\' as u16, TI89_KEY_ENTER];
+    for keycode in keys {
+        assert!(link
+            .written()
+            .windows(4)
+            .any(|window| window == key(keycode)));
+    }
+    assert!(link.written().ends_with(&key(TI89_KEY_ENTER)));
+}
+
+#[test]
+fn launch_leaves_soyuz_handshake_for_apollo_on_ti83p() {
+    let (major, minor, _) = apollo_version();
+    let mut link = MockLink::new();
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Data, vec![0x00, 0x60]),
+    );
+    push_packet(&mut link, dir_entry_83p("I68SOYUZ", PROTECTED_PROGRAM, 6));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Eot));
+    // two ACKs per key for QUIT, CLEAR twice, Asm(, prgm and the name, then just the one for ENTER
+    for _ in 0..2 * (5 + "I68SOYUZ".len()) + 1 {
+        push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    }
+    // soyuz is up and talking before the 83+ would get round to the ENTER's second ACK
+    link.push_handshake((major, minor, 0), 183);
+
+    launch(&mut link, 183, None).unwrap();
+
+    let enter = Packet::with_param(PC_TO_TI83P, Command::Cmd, TI83P_KEY_ENTER).encode();
+    assert!(link.written().ends_with(&enter));
+    assert!(I68MetaInfo::handshake(&mut link).is_ok());
+}
+
+#[test]
+fn soyuz_in_another_folder_doesnt_count() {
+    let bundle = TiVar {
+        folder: "main".to_string(),
+        name: "i68soyuz".to_string(),
+        var_type: 0x21,
+        attr: 0,
+        version: 0,
+        data: vec![0; 6],
+    };
+
+    let mut link = MockLink::new();
+    // an old copy of the same size sitting in another folder, and nothing in main
+    push_dir_ti89(
+        &mut link,
+        &[("main", TI89_DIR, 0, 0), ("old", TI89_DIR, 0, 0)],
+    );
+    push_dir_ti89(&mut link, &[("main", TI89_DIR, 0, 0)]);
+    push_dir_ti89(
+        &mut link,
+        &[("old", TI89_DIR, 0, 0), ("i68soyuz", 0x21, 0, 6)],
+    );
+    for command in [Command::Ack, Command::Cts, Command::Ack, Command::Ack] {
+        push_packet(&mut link, Packet::new(TI89_TO_PC, command));
+    }
+
+    assert_eq!(
+        install(&mut link, 89, &bundle),
+        Ok(InstallOutcome::Installed)
+    );
+    assert_eq!(link.bytes_remaining(), 0);
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use std::{
+    env, fs,
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+use i68apollo::{calc::I68MetaInfo, keyboard::CalcKey, link::socket::SocketLink, run, RunConfig};
+
+use common::RecordingKeySink;
+
+#[test]
+fn apollo_runs_against_simulated_ti92p() {
+    let socket_path =
+        env::temp_dir().join(format!("i68soyuz-sim-test-{}.sock", std::process::id()));
+
+    let mut sim = Command::new(env!("CARGO_BIN_EXE_i68soyuz-sim"))
+        .args(["--model", "92p", "--socket"])
+        .arg(&socket_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut link = None;
+    for _ in 0..100 {
+        if let Ok(socket) = SocketLink::connect(&socket_path) {
+            link = Some(socket);
+            break;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    let mut link = link.expect("simulator never started listening");
+
+    sim.stdin
+        .take()
+        .unwrap()
+        .write_all(b"# say hi\ntap H\npress Shift\ntap I\nrelease Shift\ntap ON\n")
+        .unwrap();
+
+    let mut meta = I68MetaInfo::handshake(&mut link).unwrap();
+    assert_eq!(meta.machine_id, 192);
+
+    let mut sink = RecordingKeySink::default();
+    run(
+        &mut link,
+        meta.calc_handle.as_mut(),
+        &mut sink,
+        &RunConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        sink.events,
+        vec![
+            (CalcKey::H, true),
+            (CalcKey::H, false),
+            (CalcKey::Shift, true),
+            (CalcKey::I, true),
+            (CalcKey::I, false),
+            (CalcKey::Shift, false),
+        ]
+    );
+
+    sim.wait().unwrap();
+    let _ = fs::remove_file(&socket_path);
+}
@@ -0,0 +1,116 @@
+use i68apollo::{
+    formats::VarFile,
+    tokens::{
+        detokenize_ti83p, program_to_source, source_to_program, ti83p, tokenize_ti83p, TokenError,
+    },
+};
+
+const HELLO_8XP: &[u8] = include_bytes!("samples/HELLO.8xp");
+
+fn token_bytes(token: u16) -> Vec<u8> {
+    if token > 0xFF {
+        token.to_be_bytes().to_vec()
+    } else {
+        vec![token as u8]
+    }
+}
+
+#[test]
+fn ti83p_programs_round_trip() {
+    let hello = VarFile::parse(HELLO_8XP).unwrap().into_single().unwrap();
+    let source = program_to_source(183, &hello).unwrap();
+    assert_eq!(source, "Disp \"H\"");
+
+    let sent = source_to_program(183, "main", "HELLO", &source).unwrap();
+    assert_eq!(sent.folder, "");
+    assert_eq!(sent.data, hello.data);
+
+    let source = "If A≥2\nThen\nsin(A)→B\nDisp \"hi\"\nEnd";
+    assert_eq!(detokenize_ti83p(&tokenize_ti83p(source).unwrap()), source);
+}
+
+#[test]
+fn every_ti83p_token_round_trips() {
+    for (token, text) in ti83p::TOKENS {
+        let bytes = token_bytes(*token);
+        assert_eq!(tokenize_ti83p(text).unwrap(), bytes, "{text}");
+        assert_eq!(detokenize_ti83p(&bytes), *text, "{token:#06x}");
+    }
+}
+
+#[test]
+fn ti83p_tokens_that_run_together_are_escaped() {
+    // space, o, r, space would read back as the one token " or "
+    let tokens = [0x29, 0xBB, 0xBF, 0xBB, 0xC2, 0x29];
+    let source = detokenize_ti83p(&tokens);
+    assert_eq!(source, "\\x{29}or ");
+    assert_eq!(tokenize_ti83p(&source).unwrap(), tokens);
+}
+
+#[test]
+fn whole_ti83p_programs_round_trip() {
+    // every token next to every other, so anything that runs together with its neighbour turns up somewhere
+    for (first, _) in ti83p::TOKENS {
+        let tokens = ti83p::TOKENS
+            .iter()
+            .flat_map(|(second, _)| [token_bytes(*first), token_bytes(*second)].concat())
+            .collect::<Vec<u8>>();
+        assert_eq!(
+            tokenize_ti83p(&detokenize_ti83p(&tokens)).unwrap(),
+            tokens,
+            "{first:#06x}"
+        );
+    }
+}
+
+#[test]
+fn ti83p_longest_token_wins() {
+    assert_eq!(tokenize_ti83p("sin(").unwrap(), [0xC2]);
+    assert_eq!(tokenize_ti83p("si").unwrap(), [0xBB, 0xC3, 0xBB, 0xB8]);
+}
+
+#[test]
+fn unknown_tokens_are_escaped() {
+    // a stray 0 byte and a two byte token nothing's assigned to
+    let tokens = [0x41, 0x00, 0xBB, 0xFE];
+    let source = detokenize_ti83p(&tokens);
+    assert_eq!(source, "A\\x{00}\\x{BBFE}");
+    assert_eq!(tokenize_ti83p(&source).unwrap(), tokens);
+
+    assert_eq!(tokenize_ti83p("😀"), Err(TokenError::UnknownChar('😀')));
+}
+
+#[test]
+fn ti89_source_round_trips() {
+    let source = ":hello()\n:Prgm\n:Disp \"π≠3\"\n:EndPrgm";
+    let var = source_to_program(89, "main", "hello", source).unwrap();
+    assert_eq!((var.folder.as_str(), var.var_type), ("main", 0x12));
+    assert_eq!(
+        var.data.len(),
+        u16::from_be_bytes([var.data[0], var.data[1]]) as usize + 2
+    );
+    assert!(var.data.windows(2).any(|pair| pair == [0x8C, 0x9D]));
+    assert_eq!(program_to_source(89, &var).unwrap(), source);
+}
+
+#[test]
+fn ti89_backslashes_that_look_like_escapes_are_escaped() {
+    // a string with a literal \x{41} in it, rather than an A. Backslashes anywhere else are left be
+    let source = r#":Disp "\x{5C}x{41}", main\hi"#;
+    let var = source_to_program(89, "main", "hello", source).unwrap();
+    assert!(var.data.windows(6).any(|window| window == b"\\x{41}"));
+    assert_eq!(program_to_source(89, &var).unwrap(), source);
+}
+
+#[test]
+fn tokenized_ti89_programs_are_refused() {
+    let mut var = source_to_program(89, "main", "hello", ":hello()").unwrap();
+    var.data[4] = 0xE9;
+    assert_eq!(program_to_source(89, &var), Err(TokenError::Tokenized));
+
+    var.var_type = 0x0B;
+    assert_eq!(
+        program_to_source(89, &var),
+        Err(TokenError::NotAProgram(0x0B))
+    );
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use i68apollo::{
+    formats::{TiVar, TI83P_ATTR_ARCHIVED, TI89_ATTR_ARCHIVED, TI89_ATTR_LOCKED},
+    link::{
+        mock::MockLink,
+        packet::{Command, Packet, PC_TO_TI83P, TI83P_TO_PC, TI89_TO_PC},
+    },
+    vars::{
+        delete_var, get_clock, get_var, list_vars, send_var, set_clock, CalcClock, DirEntry,
+        TransferError, TI89_DIR,
+    },
+};
+
+use common::{push_dir_ti89, push_packet};
+
+const STRNG: u8 = 0x04;
+
+fn archived_str1() -> DirEntry {
+    DirEntry {
+        folder: String::new(),
+        name: "Str1".to_string(),
+        var_type: STRNG,
+        attr: TI83P_ATTR_ARCHIVED,
+        size: 4,
+    }
+}
+
+#[test]
+fn ti83p_variables_come_back_whole() {
+    let mut link = MockLink::new();
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    // a variable that needs a later OS, which it says in its version
+    let mut header = vec![0; 13];
+    header[11] = 0x01;
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Var, header),
+    );
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI83P_TO_PC, Command::Data, vec![0x02, 0x00, 0xbb, 0xcc]),
+    );
+
+    let var = get_var(&mut link, 183, &archived_str1()).unwrap();
+
+    assert_eq!(var.name, "Str1");
+    assert_eq!(var.attr, TI83P_ATTR_ARCHIVED);
+    assert_eq!(var.version, 0x01);
+    assert_eq!(var.data, vec![0x02, 0x00, 0xbb, 0xcc]);
+    assert_eq!(link.bytes_remaining(), 0);
+
+    // asking for it has to say it's archived, or the calc goes looking in RAM
+    let request = Packet::decode(link.written()).unwrap().0;
+    assert_eq!(request.command, Command::Req);
+    assert_eq!(request.data[12], TI83P_ATTR_ARCHIVED);
+}
+
+#[test]
+fn missing_ti83p_variables_are_reported() {
+    let mut link = MockLink::new();
+    push_packet(
+        &mut link,
+        Packet::with_param(TI83P_TO_PC, Command::Ack, 0x0001),
+    );
+
+    assert_eq!(
+        get_var(&mut link, 183, &archived_str1()),
+        Err(TransferError::NotFound)
+    );
+}
+
+#[test]
+fn ti83p_deletes_with_a_del_packet() {
+    let mut link = MockLink::new();
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+    push_packet(&mut link, Packet::new(TI83P_TO_PC, Command::Ack));
+
+    delete_var(&mut link, 183, &archived_str1()).unwrap();
+
+    let request = Packet::decode(link.written()).unwrap().0;
+    assert_eq!(request.machine_id, PC_TO_TI83P);
+    assert_eq!(request.command, Command::Del);
+    assert_eq!(&request.data[3..7], b"Str1");
+}
+
+#[test]
+fn ti89_clock_round_trips() {
+    let clock = CalcClock {
+        seconds: 0x1234_5678,
+        date_format: 1,
+        time_format: 24,
+    };
+
+    let mut link = MockLink::new();
+    for command in [Command::Ack, Command::Cts, Command::Ack, Command::Ack] {
+        push_packet(&mut link, Packet::new(TI89_TO_PC, command));
+    }
+    set_clock(&mut link, 89, &clock).unwrap();
+
+    // RTS, the ACK for its CTS, then the clock itself
+    let (rts, rts_len) = Packet::decode(link.written()).unwrap();
+    assert_eq!(rts.command, Command::Rts);
+    assert_eq!(&rts.data[4..6], &[0x18, 5]);
+    assert_eq!(&rts.data[6..11], b"Clock");
+
+    let ack_len = Packet::decode(&link.written()[rts_len..]).unwrap().1;
+    let data = Packet::decode(&link.written()[rts_len + ack_len..])
+        .unwrap()
+        .0;
+    assert_eq!(data.command, Command::Data);
+    assert_eq!(&data.data[6..10], &[0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(&data.data[10..12], &[1, 24]);
+
+    // and the same bytes coming back the other way
+    let mut link = MockLink::new();
+    push_packet(&mut link, Packet::new(TI89_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI89_TO_PC, Command::Var, vec![0; 12]),
+    );
+    push_packet(&mut link, Packet::new(TI89_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(
+            TI89_TO_PC,
+            Command::Data,
+            vec![
+                0, 0, 0, 0, 0, 0, 0x12, 0x34, 0x56, 0x78, 1, 24, 0xFF, 0, 0, 0,
+            ],
+        ),
+    );
+    push_packet(&mut link, Packet::new(TI89_TO_PC, Command::Eot));
+
+    assert_eq!(get_clock(&mut link, 89), Ok(clock));
+    assert_eq!(link.bytes_remaining(), 0);
+
+    assert_eq!(
+        get_clock(&mut MockLink::new(), 183),
+        Err(TransferError::UnsupportedModel(183))
+    );
+}
+
+#[test]
+fn refused_variables_dont_hold_up_the_next() {
+    let var = |name: &str| TiVar {
+        folder: "main".to_string(),
+        name: name.to_string(),
+        var_type: STRNG,
+        attr: 0,
+        version: 0,
+        data: vec![0x00, 0x03, 0x00, b'h', b'i', 0x00, 0x2d],
+    };
+
+    let mut link = MockLink::new();
+    // the first one's locked on the calculator, so it says no with a rejection code
+    push_packet(&mut link, Packet::new(TI89_TO_PC, Command::Ack));
+    push_packet(
+        &mut link,
+        Packet::with_data(TI89_TO_PC, Command::Ske, vec![0x01, 0x01, 0x00]),
+    );
+    for command in [Command::Ack, Command::Cts, Command::Ack, Command::Ack] {
+        push_packet(&mut link, Packet::new(TI89_TO_PC, command));
+    }
+
+    assert_eq!(
+        send_var(&mut link, 89, &var("locked")),
+        Err(TransferError::Refused)
+    );
+    assert_eq!(send_var(&mut link, 89, &var("fresh")), Ok(()));
+    assert_eq!(link.bytes_remaining(), 0);
+}
+
+#[test]
+fn ti89_listings_give_the_attributes_files_use() {
+    let mut link = MockLink::new();
+    push_dir_ti89(&mut link, &[("main", TI89_DIR, 0, 0)]);
+    push_dir_ti89(
+        &mut link,
+        &[
+            ("main", TI89_DIR, 0, 0),
+            ("notes", 0x0B, 0x02, 4),
+            ("x", 0x00, 0x01, 3),
+            ("y", 0x00, 0x00, 3),
+        ],
+    );
+
+    let attrs = list_vars(&mut link, 89)
+        .unwrap()
+        .into_iter()
+        .map(|entry| (entry.name, entry.attr))
+        .collect::<Vec<(String, u8)>>();
+    assert_eq!(
+        attrs,
+        [
+            ("notes".to_string(), TI89_ATTR_ARCHIVED),
+            ("x".to_string(), TI89_ATTR_LOCKED),
+            ("y".to_string(), 0)
+        ]
+    );
+}